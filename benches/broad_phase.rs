@@ -0,0 +1,96 @@
+// Compares a full simulation step with the broad phase against testing every ball
+// against every object, on a level with 1000 bricks and 50 balls.
+//
+//     cargo bench --bench broad_phase
+
+extern crate wasm_test;
+
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use wasm_test::broad_phase::UniformGrid;
+use wasm_test::game::{GameState, WebGame};
+use wasm_test::level::{Level, load_level};
+use wasm_test::render::NullBackend;
+use wasm_test::simulation::{update, STARTING_LIVES};
+
+const COLUMNS: usize = 50;
+const ROWS: usize = 20;
+const BALLS: usize = 50;
+const STEPS: u32 = 600;
+
+fn big_level() -> String {
+	let mut balls = String::new();
+	for i in 0..BALLS {
+		if i > 0 {
+			balls.push_str(",\n");
+		}
+		let position = [-90.0 + (i % 10) as f32 * 20.0, -20.0 - (i / 10) as f32 * 10.0];
+		let velocity = [60.0 + i as f32, 80.0 - i as f32];
+		write!(balls, "\t\t{{ \"position\": [{:.1}, {:.1}], \"velocity\": [{:.1}, {:.1}] }}", position[0], position[1], velocity[0], velocity[1]).unwrap();
+	}
+	let row = format!("\"{}\"", "1".repeat(COLUMNS));
+	let grid = vec![row; ROWS].join(", ");
+	format!(r#"{{
+	"name": "Benchmark",
+	"arena": [250.0, 250.0],
+	"walls": [
+		{{ "position": [-122.5, 0.0], "size": [5.0, 250.0] }},
+		{{ "position": [122.5, 0.0], "size": [5.0, 250.0] }},
+		{{ "position": [0.0, 122.5], "size": [250.0, 5.0] }}
+	],
+	"out_of_bounds": [
+		{{ "position": [0.0, -122.5], "size": [250.0, 5.0] }}
+	],
+	"paddle": {{ "position": [0.0, -100.0], "size": [20.0, 3.0] }},
+	"balls": [
+{}
+	],
+	"brick_types": {{ "1": {{ "hit_points": 100 }} }},
+	"bricks": {{
+		"origin": [-98.0, 100.0],
+		"cell": [4.0, 2.0],
+		"size": [3.5, 1.5],
+		"grid": [{}]
+	}}
+}}"#, balls, grid)
+}
+
+fn run(level: &Level, grid_cell_size: Option<f32>) -> Duration {
+	let mut state = WebGame::new(Box::new(NullBackend));
+	load_level(&mut state, level);
+	state.game_state = GameState::Playing;
+	state.lives = STARTING_LIVES;
+	if let Some(cell_size) = grid_cell_size {
+		state.object_grid = UniformGrid::new(cell_size);
+		state.ball_grid = UniformGrid::new(cell_size);
+	}
+	assert_eq!(state.objects.len(), COLUMNS * ROWS + 4);
+	assert_eq!(state.balls.len(), BALLS);
+
+	let start = Instant::now();
+	for _ in 0..STEPS {
+		update(&mut state, 1.0 / 120.0);
+		// Like a front end, so the event queue does not grow
+		state.collision_events.dispatch();
+	}
+	start.elapsed()
+}
+
+fn per_step(duration: Duration) -> f64 {
+	duration.as_secs_f64() * 1.0e6 / f64::from(STEPS)
+}
+
+fn main() {
+	let level = Level::parse(&big_level()).expect("benchmark level is valid");
+	// Warm up
+	run(&level, None);
+
+	let grid = run(&level, None);
+	// One huge cell holds everything
+	let brute_force = run(&level, Some(1.0e9));
+	println!("{} bricks, {} balls, {} steps", COLUMNS * ROWS, BALLS, STEPS);
+	println!("uniform grid: {:>10.1} us per step", per_step(grid));
+	println!("brute force:  {:>10.1} us per step", per_step(brute_force));
+	println!("speedup:      {:>10.1}x", per_step(brute_force) / per_step(grid));
+}
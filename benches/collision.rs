@@ -0,0 +1,130 @@
+// Times collision tests, contact manifolds, time of impact and raycasts between every pair of shapes and
+// checks that none of them touches the heap.
+//
+//     cargo bench --bench collision
+
+extern crate wasm_test;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+
+use wasm_test::sat::*;
+
+// Counts every allocation made by the process
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+	unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+		ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+		System.alloc(layout)
+	}
+
+	unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+		System.dealloc(ptr, layout)
+	}
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+const ITERATIONS: u32 = 20000;
+
+fn make_shape(kind: ShapeType, position: [f32; 2], rotation: f32) -> Box<dyn Shape> {
+	let mut shape: Box<dyn Shape> = match kind {
+		ShapeType::Convex => Box::new(ConvexObject::new(position, vec![[-1.0, 1.0], [1.0, 1.0], [1.5, 0.0], [1.0, -1.0], [-1.0, -1.0]])),
+		ShapeType::Circle => Box::new(CircleObject::new(position, 1.0)),
+		ShapeType::Capsule => Box::new(CapsuleObject::new(position, 1.0, 0.5)),
+		ShapeType::Segment => Box::new(SegmentObject::new([position[0] - 1.0, position[1] - 0.5], [position[0] + 1.0, position[1] + 0.5])),
+		ShapeType::Aabb => Box::new(AabbObject::new(position, [1.0, 0.75])),
+		ShapeType::Chain => Box::new(ChainObject::new(position, vec![[-1.5, -1.0], [0.0, 1.0], [1.5, -1.0]], false)),
+		ShapeType::Compound => Box::new(CompoundObject::new(position, &[[-1.0, -1.0], [1.0, -1.0], [1.0, 0.0], [0.0, 0.0], [0.0, 1.0], [-1.0, 1.0]]).unwrap()),
+	};
+	shape.set_rotation(rotation);
+	shape
+}
+
+fn main() {
+	let kinds = [ShapeType::Convex, ShapeType::Circle, ShapeType::Capsule, ShapeType::Segment, ShapeType::Aabb, ShapeType::Chain, ShapeType::Compound];
+	let mut pairs = Vec::new();
+	for &kind_a in &kinds {
+		for &kind_b in &kinds {
+			// Overlapping and apart
+			for &offset in &[[0.5, -0.4], [6.0, 0.0]] {
+				pairs.push((make_shape(kind_a, [0.0, 0.0], 0.3), make_shape(kind_b, offset, 1.2)));
+			}
+		}
+	}
+	let ball = CircleObject::new([-8.0, 0.3], 1.0);
+
+	for &narrow_phase in &[NarrowPhase::Sat, NarrowPhase::Gjk] {
+		let mut hits = 0;
+		let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+		let start = Instant::now();
+		for _ in 0..ITERATIONS {
+			for (a, b) in &pairs {
+				if get_collision_with(a.as_ref(), b.as_ref(), narrow_phase).is_some() {
+					hits += 1;
+				}
+			}
+		}
+		let elapsed = start.elapsed();
+		let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+		let tests = ITERATIONS as usize * pairs.len();
+		println!("{:?}: {} tests, {} hits, {:.1} ns per test, {} allocations",
+			narrow_phase, tests, hits, elapsed.as_secs_f64() * 1.0e9 / tests as f64, allocations);
+		assert_eq!(allocations, 0);
+	}
+
+	let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+	let start = Instant::now();
+	let mut points = 0;
+	for _ in 0..ITERATIONS {
+		for (a, b) in &pairs {
+			if let Some(manifold) = get_contact_manifold(a.as_ref(), b.as_ref()) {
+				points += manifold.get_points().len();
+			}
+		}
+	}
+	let elapsed = start.elapsed();
+	let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+	let tests = ITERATIONS as usize * pairs.len();
+	println!("Contact manifold: {} tests, {} points, {:.1} ns per test, {} allocations",
+		tests, points, elapsed.as_secs_f64() * 1.0e9 / tests as f64, allocations);
+	assert_eq!(allocations, 0);
+
+	let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+	let start = Instant::now();
+	let mut hits = 0;
+	for _ in 0..ITERATIONS {
+		for (_, b) in &pairs {
+			if get_time_of_impact(&ball, [16.0, 0.0], b.as_ref(), [0.0, 0.0]).is_some() {
+				hits += 1;
+			}
+		}
+	}
+	let elapsed = start.elapsed();
+	let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+	let tests = ITERATIONS as usize * pairs.len();
+	println!("Time of impact: {} tests, {} hits, {:.1} ns per test, {} allocations",
+		tests, hits, elapsed.as_secs_f64() * 1.0e9 / tests as f64, allocations);
+	assert_eq!(allocations, 0);
+
+	let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+	let start = Instant::now();
+	let mut hits = 0;
+	for _ in 0..ITERATIONS {
+		for (_, b) in &pairs {
+			if raycast(b.as_ref(), [-8.0, 0.3], [1.0, 0.0], 16.0).is_some() {
+				hits += 1;
+			}
+		}
+	}
+	let elapsed = start.elapsed();
+	let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+	println!("Raycast: {} tests, {} hits, {:.1} ns per test, {} allocations",
+		tests, hits, elapsed.as_secs_f64() * 1.0e9 / tests as f64, allocations);
+	assert_eq!(allocations, 0);
+}
@@ -27,6 +27,8 @@ function prepareGeometry() {
   geometry[3] = new THREE.BoxGeometry(5, 100, 10);
   // Top and down walls
   geometry[4] = new THREE.BoxGeometry(100, 5, 10);
+  // Unit box, scaled to size by the game
+  geometry[5] = new THREE.BoxGeometry(1, 1, 1);
 }
 
 function prepareMaterials() {
@@ -55,6 +57,25 @@ function prepareMaterials() {
     color: 0xFFFFFF,
     metalness: 1.0
   });
+  // Bricks, by remaining hit points
+  materials[4] = new THREE.MeshStandardMaterial({
+    emissive: 0x101010,
+    color: 0x2E8B57,
+    metalness: 0.5,
+    roughness: 0.5
+  });
+  materials[5] = new THREE.MeshStandardMaterial({
+    emissive: 0x101010,
+    color: 0xDAA520,
+    metalness: 0.5,
+    roughness: 0.5
+  });
+  materials[6] = new THREE.MeshStandardMaterial({
+    emissive: 0x101010,
+    color: 0xB22222,
+    metalness: 0.5,
+    roughness: 0.5
+  });
 }
 
 
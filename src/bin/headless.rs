@@ -0,0 +1,195 @@
+extern crate wasm_test;
+
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::process;
+
+use wasm_test::game::{GameState, WebGame};
+use wasm_test::level::{Level, load_level, DEFAULT_LEVEL};
+use wasm_test::render::NullBackend;
+use wasm_test::simulation::{initial_render_call, FixedTimestep, DEFAULT_MAX_STEPS, DEFAULT_TICK_RATE, STARTING_LIVES};
+
+const USAGE: &str = "Runs the game simulation without a browser and prints the result as JSON
+
+Usage: headless [options]
+
+Options:
+    --level <file>     Level file to load instead of the default one
+    --frames <n>       Number of frames to simulate (default 600)
+    --dt <seconds>     Fixed frame time (default 1/60)
+    --tick-rate <hz>   Simulation steps per second (default 120)
+    --max-steps <n>    Simulation steps per frame at most (default 8)
+    --mouse-x <x>      Paddle position (default 0)
+    --trace            Print ball and paddle positions after every frame, one JSON object per line
+    --help             Show this message";
+
+struct Options {
+	level: Option<String>,
+	frames: u32,
+	dt: f64,
+	tick_rate: f64,
+	max_steps: u32,
+	mouse_x: f32,
+	trace: bool,
+}
+
+fn parse_options() -> Result<Options, String> {
+	let mut options = Options {
+		level: None,
+		frames: 600,
+		dt: 1.0 / 60.0,
+		tick_rate: DEFAULT_TICK_RATE,
+		max_steps: DEFAULT_MAX_STEPS,
+		mouse_x: 0.0,
+		trace: false,
+	};
+	let mut args = env::args().skip(1);
+	while let Some(arg) = args.next() {
+		match arg.as_str() {
+			"--level" => options.level = Some(parse_value(&arg, args.next())?),
+			"--frames" => options.frames = parse_value(&arg, args.next())?,
+			"--dt" => options.dt = parse_value(&arg, args.next())?,
+			"--tick-rate" => options.tick_rate = parse_value(&arg, args.next())?,
+			"--max-steps" => options.max_steps = parse_value(&arg, args.next())?,
+			"--mouse-x" => options.mouse_x = parse_value(&arg, args.next())?,
+			"--trace" => options.trace = true,
+			"--help" => {
+				println!("{}", USAGE);
+				process::exit(0);
+			}
+			_ => return Err(format!("unknown option '{}'", arg)),
+		}
+	}
+	if options.dt <= 0.0 {
+		return Err("--dt must be positive".to_string());
+	}
+	if options.tick_rate <= 0.0 {
+		return Err("--tick-rate must be positive".to_string());
+	}
+	if options.max_steps == 0 {
+		return Err("--max-steps must be at least 1".to_string());
+	}
+	Ok(options)
+}
+
+fn parse_value<T: std::str::FromStr>(name: &str, value: Option<String>) -> Result<T, String> {
+	let value = value.ok_or_else(|| format!("missing value for {}", name))?;
+	value.parse().map_err(|_| format!("invalid value '{}' for {}", value, name))
+}
+
+fn inside_arena(position: [f32; 2], arena: [f32; 2]) -> bool {
+	position[0].abs() < arena[0] / 2.0 && position[1].abs() < arena[1] / 2.0
+}
+
+// JSON has no representation for NaN or infinity
+fn number_json(n: f32) -> String {
+	if n.is_finite() {
+		n.to_string()
+	} else {
+		"null".to_string()
+	}
+}
+
+fn vec_json(v: [f32; 2]) -> String {
+	format!("[{}, {}]", number_json(v[0]), number_json(v[1]))
+}
+
+fn state_json(state: &WebGame) -> String {
+	let mut out = String::new();
+	let player = state.player.as_ref().unwrap();
+	write!(out, "\"state\": \"{:?}\", \"lives\": {}, \"score\": {}, ", state.game_state, state.lives, state.score.borrow().points).unwrap();
+	write!(out, "\"paddle\": {}, \"balls\": [", vec_json([player.object.position[0], player.object.position[1]])).unwrap();
+	for (i, ball) in state.balls.iter().enumerate() {
+		if i > 0 {
+			out.push_str(", ");
+		}
+		write!(out, "{{\"position\": {}, \"velocity\": {}}}", vec_json(ball.get_position()), vec_json(ball.get_velocity())).unwrap();
+	}
+	out.push(']');
+	out
+}
+
+fn main() {
+	let options = match parse_options() {
+		Ok(options) => options,
+		Err(error) => {
+			eprintln!("error: {}\n\n{}", error, USAGE);
+			process::exit(2);
+		}
+	};
+
+	let mut state = WebGame::new(Box::new(NullBackend));
+	let arena = match options.level {
+		Some(ref path) => {
+			let level = fs::read_to_string(path)
+				.map_err(|error| error.to_string())
+				.and_then(|source| Level::parse(&source).map_err(|error| error.to_string()));
+			match level {
+				Ok(level) => {
+					load_level(&mut state, &level);
+					level.arena
+				}
+				Err(error) => {
+					eprintln!("error: {}: {}", path, error);
+					process::exit(1);
+				}
+			}
+		}
+		None => {
+			initial_render_call(&mut state);
+			Level::parse(DEFAULT_LEVEL).expect("built in levels are valid").arena
+		}
+	};
+	// Skip the attract and serve states, balls move from the first frame
+	state.game_state = GameState::Playing;
+	state.score.borrow_mut().on_launch(0.0);
+	if state.lives == 0 {
+		state.lives = STARTING_LIVES;
+	}
+	state.mouse_x = options.mouse_x;
+	let mut timestep = FixedTimestep::new(options.tick_rate, options.max_steps);
+
+	// By ball id, a ball escapes when it leaves the arena without being lost
+	let mut inside: HashMap<u32, bool> = HashMap::new();
+	let mut object_collisions = 0;
+	let mut ball_collisions = 0;
+	let mut balls_lost = 0;
+	let mut escapes = 0;
+	for frame in 1..(options.frames + 1) {
+		// A new ball is served right away
+		if state.game_state == GameState::Serve {
+			state.on_button(" ", true);
+			state.on_button(" ", false);
+		}
+		for ball in &state.balls {
+			inside.entry(ball.get_id()).or_insert_with(|| inside_arena(ball.get_position(), arena));
+		}
+
+		state.current_time_in_seconds = f64::from(frame) * options.dt;
+		let stats = timestep.advance(&mut state, options.dt);
+		state.collision_events.dispatch();
+		object_collisions += stats.object_collisions;
+		ball_collisions += stats.ball_collisions;
+		balls_lost += stats.balls_lost;
+
+		for ball in &state.balls {
+			let now_inside = inside_arena(ball.get_position(), arena);
+			let inside = inside.entry(ball.get_id()).or_insert(now_inside);
+			if *inside && !now_inside {
+				escapes += 1;
+			}
+			*inside = now_inside;
+		}
+
+		if options.trace {
+			println!("{{\"frame\": {}, \"time\": {}, {}}}", frame, state.current_time_in_seconds, state_json(&state));
+		}
+	}
+
+	if !options.trace {
+		println!("{{\"frames\": {}, \"dt\": {}, \"object_collisions\": {}, \"ball_collisions\": {}, \"balls_lost\": {}, \"escapes\": {}, \"final\": {{{}}}}}",
+			options.frames, options.dt, object_collisions, ball_collisions, balls_lost, escapes, state_json(&state));
+	}
+}
@@ -0,0 +1,107 @@
+use std::collections::HashMap;
+use vecmath::*;
+
+use sat::Aabb;
+
+// Uniform spatial hash, finds what might touch before the exact SAT test.
+// Cells are kept between frames so rebuilding it does not allocate once warmed up
+#[derive(Debug)]
+pub struct UniformGrid {
+	cell_size: f32,
+	cells: HashMap<(i32, i32), Vec<usize>>,
+	// Cells with something in them, so clear does not walk every cell ever used
+	used_cells: Vec<(i32, i32)>,
+}
+
+impl UniformGrid {
+	pub fn new(cell_size: f32) -> Self {
+		assert!(cell_size > 0.0);
+		UniformGrid {
+			cell_size,
+			cells: HashMap::new(),
+			used_cells: Vec::new(),
+		}
+	}
+
+	pub fn get_cell_size(&self) -> f32 {
+		self.cell_size
+	}
+
+	pub fn clear(&mut self) {
+		for cell in self.used_cells.drain(..) {
+			if let Some(indices) = self.cells.get_mut(&cell) {
+				indices.clear();
+			}
+		}
+	}
+
+	pub fn insert(&mut self, index: usize, aabb: &Aabb) {
+		let (from, to) = self.cell_range(aabb);
+		for x in from[0]..(to[0] + 1) {
+			for y in from[1]..(to[1] + 1) {
+				let indices = self.cells.entry((x, y)).or_default();
+				if indices.is_empty() {
+					self.used_cells.push((x, y));
+				}
+				indices.push(index);
+			}
+		}
+	}
+
+	// Indices of everything sharing a cell with aabb, sorted and without duplicates
+	pub fn query(&self, aabb: &Aabb, result: &mut Vec<usize>) {
+		result.clear();
+		let (from, to) = self.cell_range(aabb);
+		for x in from[0]..(to[0] + 1) {
+			for y in from[1]..(to[1] + 1) {
+				if let Some(indices) = self.cells.get(&(x, y)) {
+					result.extend_from_slice(indices);
+				}
+			}
+		}
+		result.sort_unstable();
+		result.dedup();
+	}
+
+	fn cell_range(&self, aabb: &Aabb) -> (Vector2<i32>, Vector2<i32>) {
+		let cell = |v: f32| (v / self.cell_size).floor() as i32;
+		([cell(aabb.min[0]), cell(aabb.min[1])], [cell(aabb.max[0]), cell(aabb.max[1])])
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_query() {
+		let mut grid = UniformGrid::new(10.0);
+		grid.insert(0, &Aabb::new([-50.0, -2.5], [50.0, 2.5]));
+		grid.insert(1, &Aabb::new([12.0, 12.0], [14.0, 14.0]));
+		grid.insert(2, &Aabb::new([-14.0, 12.0], [-12.0, 14.0]));
+
+		let mut result = Vec::new();
+		grid.query(&Aabb::new([11.0, 1.0], [13.0, 13.0]), &mut result);
+		assert_eq!(result, vec![0, 1]);
+
+		grid.query(&Aabb::new([-30.0, 11.0], [30.0, 13.0]), &mut result);
+		assert_eq!(result, vec![1, 2]);
+
+		grid.query(&Aabb::new([100.0, 100.0], [101.0, 101.0]), &mut result);
+		assert!(result.is_empty());
+	}
+
+	#[test]
+	fn test_clear() {
+		let mut grid = UniformGrid::new(10.0);
+		grid.insert(0, &Aabb::new([0.0, 0.0], [1.0, 1.0]));
+		grid.clear();
+		let mut result = Vec::new();
+		grid.query(&Aabb::new([0.0, 0.0], [1.0, 1.0]), &mut result);
+		assert!(result.is_empty());
+
+		grid.insert(3, &Aabb::new([0.0, 0.0], [1.0, 1.0]));
+		grid.query(&Aabb::new([0.0, 0.0], [1.0, 1.0]), &mut result);
+		assert_eq!(result, vec![3]);
+	}
+}
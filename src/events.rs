@@ -0,0 +1,203 @@
+use vecmath::*;
+
+use game::CollidableKind;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ContactPhase {
+	// First step the pair touched
+	Begin,
+	// Still touching since the step before
+	Stay,
+	// Touched in the step before but not in this one, the contact is the last one seen
+	End,
+}
+
+// What a participant of a contact is
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Participant {
+	Object(CollidableKind),
+	Ball,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Contact {
+	// A ball, or the paddle moving into a sensor
+	pub a: u32,
+	pub a_type: Participant,
+	pub b: u32,
+	pub b_type: Participant,
+	// World space, on the surface of a
+	pub point: Vector2<f32>,
+	// Pushes a away from b
+	pub normal: Vector2<f32>,
+	// Velocity of a minus that of b, before the collision changed them
+	pub relative_velocity: Vector2<f32>,
+	// Set when the contact destroyed b, to the points it was worth
+	pub destroyed_for_points: Option<u32>,
+}
+
+impl Contact {
+	fn same_pair(&self, other: &Contact) -> bool {
+		(self.a == other.a && self.b == other.b) || (self.a == other.b && self.b == other.a)
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CollisionEvent {
+	pub phase: ContactPhase,
+	pub contact: Contact,
+}
+
+pub type Subscriber = Box<dyn FnMut(&CollisionEvent)>;
+
+// Queue of contact events, filled by every simulation step and drained once per frame.
+// Scoring, sounds or particles can poll it with take_events or subscribe
+#[derive(Default)]
+pub struct CollisionEvents {
+	events: Vec<CollisionEvent>,
+	// Touching in the last step, and in the current one so far
+	previous: Vec<Contact>,
+	current: Vec<Contact>,
+	subscribers: Vec<Subscriber>,
+}
+
+impl CollisionEvents {
+	pub fn new() -> Self {
+		CollisionEvents::default()
+	}
+
+	// Only the first contact of a pair in a step is kept, but a later one destroying b still marks it
+	pub fn add_contact(&mut self, contact: Contact) {
+		match self.current.iter_mut().find(|current| current.same_pair(&contact)) {
+			Some(current) => {
+				if current.destroyed_for_points.is_none() {
+					current.destroyed_for_points = contact.destroyed_for_points;
+				}
+			}
+			None => self.current.push(contact),
+		}
+	}
+
+	// Contacts of the step so far, for rules that have to act within the step
+	pub fn get_step_contacts(&self) -> &[Contact] {
+		&self.current
+	}
+
+	// Turns the contacts of the step into events, called once at the end of every step
+	pub fn end_step(&mut self) {
+		for contact in &self.current {
+			let phase = if self.previous.iter().any(|previous| previous.same_pair(contact)) { ContactPhase::Stay } else { ContactPhase::Begin };
+			self.events.push(CollisionEvent { phase, contact: *contact });
+		}
+		for contact in &self.previous {
+			if !self.current.iter().any(|current| current.same_pair(contact)) {
+				self.events.push(CollisionEvent { phase: ContactPhase::End, contact: *contact });
+			}
+		}
+		self.previous.clear();
+		self.previous.append(&mut self.current);
+	}
+
+	// Forgets the contacts in progress without ending them, for when the level changes
+	pub fn reset_contacts(&mut self) {
+		self.previous.clear();
+		self.current.clear();
+	}
+
+	pub fn subscribe(&mut self, subscriber: Subscriber) {
+		self.subscribers.push(subscriber);
+	}
+
+	// Events since the last take_events or dispatch, oldest first
+	pub fn take_events(&mut self) -> Vec<CollisionEvent> {
+		std::mem::take(&mut self.events)
+	}
+
+	// Hands the queued events to every subscriber and empties the queue, once per frame
+	pub fn dispatch(&mut self) {
+		for event in &self.events {
+			for subscriber in &mut self.subscribers {
+				subscriber(event);
+			}
+		}
+		self.events.clear();
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use std::cell::RefCell;
+	use std::rc::Rc;
+
+	fn contact(a: u32, b: u32) -> Contact {
+		Contact {
+			a,
+			a_type: Participant::Ball,
+			b,
+			b_type: Participant::Object(CollidableKind::Wall),
+			point: [0.0, 0.0],
+			normal: [0.0, 1.0],
+			relative_velocity: [0.0, -1.0],
+			destroyed_for_points: None,
+		}
+	}
+
+	fn phases(events: &mut CollisionEvents) -> Vec<(ContactPhase, u32, u32)> {
+		events.take_events().iter().map(|event| (event.phase, event.contact.a, event.contact.b)).collect()
+	}
+
+	#[test]
+	fn test_contact_phases() {
+		let mut events = CollisionEvents::new();
+		events.add_contact(contact(1, 10));
+		events.add_contact(contact(1, 10));
+		events.add_contact(contact(2, 3));
+		assert_eq!(events.get_step_contacts().len(), 2);
+		events.end_step();
+		assert!(events.get_step_contacts().is_empty());
+		assert_eq!(phases(&mut events), vec![(ContactPhase::Begin, 1, 10), (ContactPhase::Begin, 2, 3)]);
+
+		// The same pair of balls either way round
+		events.add_contact(contact(1, 10));
+		events.add_contact(contact(3, 2));
+		events.end_step();
+		assert_eq!(phases(&mut events), vec![(ContactPhase::Stay, 1, 10), (ContactPhase::Stay, 3, 2)]);
+
+		events.add_contact(contact(3, 2));
+		events.end_step();
+		events.end_step();
+		assert_eq!(phases(&mut events), vec![(ContactPhase::Stay, 3, 2), (ContactPhase::End, 1, 10), (ContactPhase::End, 3, 2)]);
+
+		events.end_step();
+		assert!(events.take_events().is_empty());
+
+		// Destroyed by the second hit of the step
+		events.add_contact(contact(1, 10));
+		events.add_contact(Contact { destroyed_for_points: Some(10), ..contact(1, 10) });
+		events.end_step();
+		assert_eq!(events.take_events()[0].contact.destroyed_for_points, Some(10));
+	}
+
+	#[test]
+	fn test_dispatch() {
+		let mut events = CollisionEvents::new();
+		let seen = Rc::new(RefCell::new(Vec::new()));
+		let subscriber = seen.clone();
+		events.subscribe(Box::new(move |event: &CollisionEvent| subscriber.borrow_mut().push(event.phase)));
+
+		events.add_contact(contact(1, 10));
+		events.end_step();
+		events.end_step();
+		events.dispatch();
+		assert_eq!(*seen.borrow(), vec![ContactPhase::Begin, ContactPhase::End]);
+		assert!(events.take_events().is_empty());
+
+		// Forgotten contacts do not end
+		events.add_contact(contact(1, 10));
+		events.end_step();
+		events.reset_contacts();
+		events.end_step();
+		assert_eq!(phases(&mut events), vec![(ContactPhase::Begin, 1, 10)]);
+	}
+}
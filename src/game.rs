@@ -1,19 +1,58 @@
+use std::cell::RefCell;
 use std::collections::HashSet;
+use std::f32;
+use std::rc::Rc;
 use vecmath::*;
 use super::sat::*;
+use super::broad_phase::UniformGrid;
+use super::events::{CollisionEvent, CollisionEvents, Contact, Participant};
+use super::render::RenderBackend;
+use super::score::Score;
+use super::utils::{min, max};
+
+// Sweeps per ball update, time left after the last one is dropped
+const MAX_SWEEPS: u32 = 4;
+// Distance kept from a surface the ball was swept against
+const SWEEP_SKIN: f32 = 0.001;
+// A little larger than a brick
+const BROAD_PHASE_CELL_SIZE: f32 = 10.0;
+// From straight up, for a ball landing on the very end of the paddle
+const MAX_BOUNCE_ANGLE: f32 = f32::consts::FRAC_PI_3;
+// Part of the paddle's horizontal velocity given to the ball
+const PADDLE_VELOCITY_INFLUENCE: f32 = 0.3;
+// Part of the speed that always goes up or down, so a ball can not bounce between the side walls forever
+const MIN_VERTICAL_SPEED: f32 = 0.25;
 
 pub struct WebGame {
 	pub global_object_counter: u32,
 	pub buttons: HashSet<ButtonState>,
+	// Presses since the last update, drained by it
+	pub pressed_buttons: HashSet<ButtonState>,
+
+	pub game_state: GameState,
+	// State to go back to when unpausing
+	pub resume_state: GameState,
+	pub level_index: usize,
+	pub lives: u32,
+	// Shared with the collision event subscriber keeping it, see subscribe_score
+	pub score: Rc<RefCell<Score>>,
+	// Contacts of every step, drained once per frame
+	pub collision_events: CollisionEvents,
 
 	pub scene: Scene,
+	pub backend: Box<dyn RenderBackend>,
 
 	pub player: Option<CollidableObject>,
 	pub balls: Vec<BallObject>,
 	pub objects: Vec<Box<CollidableObject>>,
+	// Rebuilt every step, kept around to reuse their memory
+	pub object_grid: UniformGrid,
+	pub ball_grid: UniformGrid,
 
 	pub mouse_x: f32,
 	pub mouse_y: f32,
+	// Where the mouse was in the last step the paddle followed it, None when it did not
+	pub last_mouse_x: Option<f32>,
 	pub screen_w: i32,
 	pub screen_h: i32,
 	pub frustum_size: f32,
@@ -24,23 +63,42 @@ pub struct WebGame {
 }
 
 impl WebGame {
-	pub fn new() -> Self {
-		WebGame {
+	pub fn new(backend: Box<dyn RenderBackend>) -> Self {
+		let mut result = WebGame {
 			global_object_counter: 0,
 			buttons: HashSet::new(),
-			scene: Scene {},
+			pressed_buttons: HashSet::new(),
+			game_state: GameState::Attract,
+			resume_state: GameState::Playing,
+			level_index: 0,
+			lives: 0,
+			score: Rc::new(RefCell::new(Score::new())),
+			collision_events: CollisionEvents::new(),
+			scene: Scene::new(),
+			backend,
 			player: None,
 			balls: Vec::new(),
 			objects: Vec::new(),
+			object_grid: UniformGrid::new(BROAD_PHASE_CELL_SIZE),
+			ball_grid: UniformGrid::new(BROAD_PHASE_CELL_SIZE),
 			mouse_x: 0.0,
 			mouse_y: 0.0,
+			last_mouse_x: None,
 			screen_w: 0,
 			screen_h: 0,
 			frustum_size: 100.0,
 			materials: Materials::new(),
 			geometries: Geometries::new(),
 			current_time_in_seconds: 0.0,
-		}
+		};
+		result.subscribe_score();
+		result
+	}
+
+	// The score follows the collision events, so it changes when the events are dispatched
+	fn subscribe_score(&mut self) {
+		let score = self.score.clone();
+		self.collision_events.subscribe(Box::new(move |event: &CollisionEvent| score.borrow_mut().on_collision_event(event)));
 	}
 
 	pub fn increment_global_object_counter(&mut self) -> u32 {
@@ -49,6 +107,70 @@ impl WebGame {
 		old_value
 	}
 
+	// Pushes scene changes and dirty transforms to the backend, then renders a frame.
+	// alpha blends between the previous and the current simulation step, see FixedTimestep
+	pub fn render(&mut self, alpha: f32) {
+		for change in self.scene.take_changes() {
+			match change {
+				SceneChange::Add { id, geometry, material, cast_shadows, receive_shadows } => {
+					self.backend.create_mesh(id, geometry, material, cast_shadows, receive_shadows);
+					self.backend.add_to_scene(id);
+				}
+				SceneChange::Remove(id) => self.backend.remove_from_scene(id),
+				SceneChange::SetMaterial { id, material } => self.backend.set_material(id, material),
+				SceneChange::Clear => self.backend.clear_scene(),
+			}
+		}
+
+		let player = self.player.iter_mut().map(|player| &mut player.object);
+		let balls = self.balls.iter_mut().map(|ball| &mut ball.object);
+		let objects = self.objects.iter_mut().map(|obj| &mut obj.object);
+		for object in player.chain(balls).chain(objects) {
+			if (object.need_update || object.is_interpolating()) && self.scene.contains(object) {
+				let (position, rotation) = object.get_interpolated_transform(alpha);
+				self.backend.set_transform(object.get_id(), position, rotation, object.scale);
+				object.need_update = false;
+			}
+		}
+		self.backend.render();
+	}
+
+	// Called before every simulation step so render can interpolate from there
+	pub fn save_previous_state(&mut self) {
+		let player = self.player.iter_mut().map(|player| &mut player.object);
+		let balls = self.balls.iter_mut().map(|ball| &mut ball.object);
+		let objects = self.objects.iter_mut().map(|obj| &mut obj.object);
+		for object in player.chain(balls).chain(objects) {
+			object.save_previous_state();
+		}
+	}
+
+	// First collidable along the ray that passes the filter. Balls and destroyed objects are not included
+	pub fn raycast<F: Fn(&CollidableObject) -> bool>(&self, origin: Vector2<f32>, direction: Vector2<f32>, max_distance: f32, filter: F) -> Option<WorldHit> {
+		self.first_hit(filter, |shape| raycast(shape, origin, direction, max_distance))
+	}
+
+	// Same as raycast for a circle moving along the ray, like a ball would
+	pub fn circle_cast<F: Fn(&CollidableObject) -> bool>(&self, origin: Vector2<f32>, radius: f32, direction: Vector2<f32>, max_distance: f32, filter: F) -> Option<WorldHit> {
+		self.first_hit(filter, |shape| circle_cast(shape, origin, radius, direction, max_distance))
+	}
+
+	fn first_hit<F, C>(&self, filter: F, cast: C) -> Option<WorldHit>
+		where F: Fn(&CollidableObject) -> bool, C: Fn(&dyn Shape) -> Option<RayHit> {
+		let mut result: Option<WorldHit> = None;
+		for object in self.player.iter().chain(self.objects.iter().map(|obj| obj.as_ref())) {
+			if object.is_destroyed() || !filter(object) {
+				continue;
+			}
+			if let Some(hit) = cast(object.get_shape()) {
+				if result.is_none_or(|best| hit.distance < best.hit.distance) {
+					result = Some(WorldHit { id: object.object.get_id(), kind: object.get_kind(), hit });
+				}
+			}
+		}
+		result
+	}
+
 	pub fn on_button(&mut self, key: &str, pressed: bool) -> bool {
 		//js!(console.log("Got button: ", @{format!("{} - pressed: {}", key, pressed)}););
 		let button = match key {
@@ -63,33 +185,55 @@ impl WebGame {
 			_ => return false
 		};
 		if pressed {
-			self.buttons.insert(button);
+			// Key repeat sends more key downs while the button is held
+			if self.buttons.insert(button) {
+				self.pressed_buttons.insert(button);
+			}
 		} else {
 			self.buttons.remove(&button);
 		}
-		return true;
+		true
 	}
 }
 
+// Result of WebGame::raycast and circle_cast
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WorldHit {
+	pub id: u32,
+	pub kind: CollidableKind,
+	pub hit: RayHit,
+}
+
 #[derive(Debug)]
 pub struct GameObject {
 	id: u32,
+	geometry: u32,
+	material: u32,
+	cast_shadows: bool,
+	receive_shadows: bool,
 	pub position: Vector3<f32>,
+	// Around the z axis, counter clock wise in radians
+	pub rotation: f32,
+	pub scale: Vector3<f32>,
+	// Set when the render backend has to sync the transform
 	pub need_update: bool,
+	// Position and rotation before the last simulation step, None for new objects
+	previous: Option<(Vector3<f32>, f32)>,
 }
 
 impl GameObject {
 	pub fn new(id: u32, geometry: &Geometry, material: &Material, cast_shadows: bool, receive_shadows: bool) -> Self {
-		js!(
-			var temp = new THREE.Mesh(geometry[@{geometry.get_id()}], materials[@{material.get_id()}]);
-			temp.castShadow = @{cast_shadows};
-			temp.receiveShadow = @{receive_shadows};
-			gameObjects[@{id}] = temp;
-		);
 		GameObject {
-			id: id,
+			id,
+			geometry: geometry.get_id(),
+			material: material.get_id(),
+			cast_shadows,
+			receive_shadows,
 			position: [0.0, 0.0, 0.0],
+			rotation: 0.0,
+			scale: [1.0, 1.0, 1.0],
 			need_update: true,
+			previous: None,
 		}
 	}
 
@@ -97,44 +241,192 @@ impl GameObject {
 		self.id
 	}
 
+	pub fn get_geometry_id(&self) -> u32 {
+		self.geometry
+	}
+
+	pub fn get_material_id(&self) -> u32 {
+		self.material
+	}
+
+	pub fn casts_shadows(&self) -> bool {
+		self.cast_shadows
+	}
+
+	pub fn receives_shadows(&self) -> bool {
+		self.receive_shadows
+	}
+
 	pub fn update(&mut self) {
-		if self.need_update {
-			js!(
-				var obj = gameObjects[@{self.get_id()}];
-				obj.position.x = @{self.position[0]};
-				obj.position.y = @{self.position[1]};
-				obj.position.z = @{self.position[2]};
-			);
+		self.need_update = true;
+	}
+
+	pub fn save_previous_state(&mut self) {
+		self.previous = Some((self.position, self.rotation));
+	}
+
+	// Moved during the last simulation step, so the rendered transform changes with alpha
+	pub fn is_interpolating(&self) -> bool {
+		match self.previous {
+			Some((position, rotation)) => position != self.position || rotation != self.rotation,
+			None => false,
+		}
+	}
+
+	// alpha 0 is the previous state and 1 the current one
+	pub fn get_interpolated_transform(&self, alpha: f32) -> (Vector3<f32>, f32) {
+		match self.previous {
+			Some((position, rotation)) => (
+				vec3_add(position, vec3_scale(vec3_sub(self.position, position), alpha)),
+				rotation + (self.rotation - rotation) * alpha,
+			),
+			None => (self.position, self.rotation),
 		}
 	}
 }
 
 pub trait Collidable {
-	fn on_collision(&mut self, scene: &mut Scene, disp: &Vector2<f32>,  normal: &Vector2<f32>);
+	// Changes to the object itself, anything reacting to hits in general listens to WebGame::collision_events
+	fn on_collision(&mut self, _object: &mut GameObject, _scene: &mut Scene, _disp: &Vector2<f32>, _normal: &Vector2<f32>) {
+	}
+
+	fn get_kind(&self) -> CollidableKind;
+
+	// Starting value of CollidableObject::sensor, see there
+	fn is_solid(&self) -> bool {
+		true
+	}
+
+	// Destroyed objects are removed from WebGame::objects at the end of the frame
+	fn is_destroyed(&self) -> bool {
+		false
+	}
+
+	// Awarded when the object is destroyed
+	fn get_points(&self) -> u32 {
+		0
+	}
+
+	// Velocity of a ball at position after bouncing off the object, mirrored by default
+	fn bounce(&self, _object: &GameObject, _object_velocity: Vector2<f32>, _position: Vector2<f32>, velocity: Vector2<f32>, normal: Vector2<f32>) -> Vector2<f32> {
+		reflect(velocity, normal)
+	}
+}
+
+pub fn reflect(velocity: Vector2<f32>, normal: Vector2<f32>) -> Vector2<f32> {
+	vec2_sub(velocity, vec2_scale(normal, vec2_dot(velocity, normal) * 2.0))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CollidableKind {
+	Player,
+	Wall,
+	Brick,
+	OutOfBounds,
+}
+
+impl CollidableKind {
+	// Objects start out on the layer of their kind
+	pub fn get_layer(&self) -> u32 {
+		match *self {
+			CollidableKind::Player => LAYER_PLAYER,
+			CollidableKind::Wall => LAYER_WALL,
+			CollidableKind::Brick => LAYER_BRICK,
+			CollidableKind::OutOfBounds => LAYER_OUT_OF_BOUNDS,
+		}
+	}
+}
+
+// Collision layer bits, for CollisionFilter
+pub const LAYER_PLAYER: u32 = 1;
+pub const LAYER_WALL: u32 = 1 << 1;
+pub const LAYER_BRICK: u32 = 1 << 2;
+pub const LAYER_OUT_OF_BOUNDS: u32 = 1 << 3;
+pub const LAYER_BALL: u32 = 1 << 4;
+pub const ALL_LAYERS: u32 = !0;
+
+// Two things collide only when the mask of each has a bit of the layer of the other
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CollisionFilter {
+	pub layer: u32,
+	pub mask: u32,
+}
+
+impl CollisionFilter {
+	pub fn new(layer: u32, mask: u32) -> Self {
+		CollisionFilter { layer, mask }
+	}
+
+	pub fn collides_with(&self, other: &CollisionFilter) -> bool {
+		self.mask & other.layer != 0 && other.mask & self.layer != 0
+	}
 }
 
 pub struct CollidableObject {
 	pub object: GameObject,
-	shape: Box<Shape>,
-	collision_response: Box<Collidable>,
+	// Per second, only the paddle moves for now
+	pub velocity: Vector2<f32>,
+	// How balls test against the shape
+	pub narrow_phase: NarrowPhase,
+	// On the layer of its kind and colliding with everything by default
+	pub filter: CollisionFilter,
+	// Sensors report overlaps through on_collision but do not push balls back or bounce them.
+	// Unlike solid objects they also report the paddle moving into them
+	pub sensor: bool,
+	shape: Box<dyn Shape>,
+	collision_response: Box<dyn Collidable>,
 }
 
 impl CollidableObject {
-	pub fn new(object: GameObject, shape: Box<Shape>, collision_response: Box<Collidable>) -> Self {
+	pub fn new(object: GameObject, shape: Box<dyn Shape>, collision_response: Box<dyn Collidable>) -> Self {
 		CollidableObject {
-			object: object,
-			shape: shape,
-			collision_response: collision_response,
+			object,
+			velocity: [0.0, 0.0],
+			narrow_phase: NarrowPhase::default(),
+			filter: CollisionFilter::new(collision_response.get_kind().get_layer(), ALL_LAYERS),
+			sensor: !collision_response.is_solid(),
+			shape,
+			collision_response,
 		}
 	}
 
 	pub fn update(&mut self) {
 		self.object.update();
 		self.shape.set_position([self.object.position[0], self.object.position[1]]);
+		self.shape.set_rotation(self.object.rotation);
+		self.shape.set_scale([self.object.scale[0], self.object.scale[1]]);
+	}
+
+	pub fn on_collision(&mut self, scene: &mut Scene, disp: &Vector2<f32>, normal: &Vector2<f32>) {
+		self.collision_response.on_collision(&mut self.object, scene, disp, normal);
+	}
+
+	pub fn get_kind(&self) -> CollidableKind {
+		self.collision_response.get_kind()
+	}
+
+	pub fn get_shape(&self) -> &dyn Shape {
+		self.shape.as_ref()
+	}
+
+	pub fn is_solid(&self) -> bool {
+		!self.sensor
+	}
+
+	pub fn is_destroyed(&self) -> bool {
+		self.collision_response.is_destroyed()
+	}
+
+	pub fn get_points(&self) -> u32 {
+		self.collision_response.get_points()
+	}
+
+	pub fn bounce(&self, position: Vector2<f32>, velocity: Vector2<f32>, normal: Vector2<f32>) -> Vector2<f32> {
+		self.collision_response.bounce(&self.object, self.velocity, position, velocity, normal)
 	}
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Material {
 	id: u32,
 }
@@ -142,7 +434,7 @@ pub struct Material {
 impl Material {
 	pub fn new(id: u32) -> Self {
 		Material {
-			id: id,
+			id,
 		}
 	}
 
@@ -159,7 +451,7 @@ pub struct Geometry {
 impl Geometry {
 	pub fn new(id: u32) -> Self {
 		Geometry {
-			id: id,
+			id,
 		}
 	}
 
@@ -168,7 +460,7 @@ impl Geometry {
 	}
 }
 
-#[derive(Debug, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum ButtonState {
 	Left,
 	Right,
@@ -176,12 +468,34 @@ pub enum ButtonState {
 	Esc,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GameState {
+	// Level is shown but nothing moves until Space is pressed
+	Attract,
+	// Balls stick to the paddle until Space launches them
+	Serve,
+	Playing,
+	Paused,
+	// Every brick is destroyed, Space continues with the next level
+	LevelComplete,
+	// Every ball is lost, Space starts over
+	GameOver,
+}
+
 #[derive(Debug)]
 pub struct Materials {
 	pub default: Material,
 	pub player: Material,
 	pub ball: Material,
 	pub walls: Material,
+	// Indexed by remaining hit points - 1
+	pub bricks: Vec<Material>,
+}
+
+impl Default for Materials {
+	fn default() -> Self {
+		Self::new()
+	}
 }
 
 impl Materials {
@@ -191,6 +505,7 @@ impl Materials {
 			player: Material::new(1),
 			ball: Material::new(2),
 			walls: Material::new(3),
+			bricks: vec![Material::new(4), Material::new(5), Material::new(6)],
 		}			
 	}
 }
@@ -202,6 +517,14 @@ pub struct Geometries {
 	pub sphere_geometry: Geometry,
 	pub side_walls_geometry: Geometry,
 	pub top_down_walls_geometry: Geometry,
+	// 1x1x1, scaled to size
+	pub unit_box_geometry: Geometry,
+}
+
+impl Default for Geometries {
+	fn default() -> Self {
+		Self::new()
+	}
 }
 
 impl Geometries {
@@ -212,36 +535,76 @@ impl Geometries {
 			sphere_geometry: Geometry::new(2),
 			side_walls_geometry: Geometry::new(3),
 			top_down_walls_geometry: Geometry::new(4),
+			unit_box_geometry: Geometry::new(5),
 		}			
 	}
 }
 
-#[derive(Debug)]
+// Changes to the scene graph, consumed by WebGame::render
+#[derive(Debug, PartialEq)]
+pub enum SceneChange {
+	Add {
+		id: u32,
+		geometry: u32,
+		material: u32,
+		cast_shadows: bool,
+		receive_shadows: bool,
+	},
+	Remove(u32),
+	SetMaterial {
+		id: u32,
+		material: u32,
+	},
+	Clear,
+}
+
+#[derive(Debug, Default)]
 pub struct Scene {
+	objects: HashSet<u32>,
+	changes: Vec<SceneChange>,
 }
 
 impl Scene {
-	pub fn clear(&self) {
-		js!(
-			while(scene.children.length > 0) { 
-				scene.remove(scene.children[0]); 
-			}
-		);
+	pub fn new() -> Self {
+		Scene::default()
 	}
 
-	pub fn add(&self, object: &mut GameObject) {
-		js!(
-			var obj = gameObjects[@{object.get_id()}];
-			scene.add(obj);
-		);
-		object.need_update = true;
+	pub fn clear(&mut self) {
+		self.objects.clear();
+		self.changes.push(SceneChange::Clear);
+	}
+
+	pub fn add(&mut self, object: &mut GameObject) {
+		self.objects.insert(object.get_id());
+		self.changes.push(SceneChange::Add {
+			id: object.get_id(),
+			geometry: object.get_geometry_id(),
+			material: object.get_material_id(),
+			cast_shadows: object.casts_shadows(),
+			receive_shadows: object.receives_shadows(),
+		});
 		object.update();
 	}
 
-	pub fn remove(&self, object: &GameObject) {
-		js!(
-			scene.remove(gameObjects[@{object.get_id()}]);
-		);
+	pub fn remove(&mut self, object: &GameObject) {
+		self.objects.remove(&object.get_id());
+		self.changes.push(SceneChange::Remove(object.get_id()));
+	}
+
+	pub fn set_material(&mut self, object: &mut GameObject, material: &Material) {
+		object.material = material.get_id();
+		self.changes.push(SceneChange::SetMaterial {
+			id: object.get_id(),
+			material: material.get_id(),
+		});
+	}
+
+	pub fn contains(&self, object: &GameObject) -> bool {
+		self.objects.contains(&object.get_id())
+	}
+
+	pub fn take_changes(&mut self) -> Vec<SceneChange> {
+		std::mem::take(&mut self.changes)
 	}
 }
 
@@ -250,61 +613,299 @@ pub struct BallObject {
 	object: GameObject,
 	shape: CircleObject,
 	v: Vector2<f32>,
+	mass: f32,
+	// 1 keeps all the speed in ball to ball collisions, 0 keeps none along the normal
+	restitution: f32,
+	filter: CollisionFilter,
+	// Where the last update started, ball to ball sweeps go from here to the current position
+	start: Vector2<f32>,
 }
 
 impl BallObject {
 	pub fn new(object: GameObject, radius: f32) -> Self {
-		let shape = CircleObject::new([object.position[0], object.position[1]], radius);
+		let position = [object.position[0], object.position[1]];
+		let mut shape = CircleObject::new(position, radius);
+		shape.set_scale([object.scale[0], object.scale[1]]);
 		BallObject {
-			object: object,
-			shape: shape,
+			object,
+			shape,
 			v: [85.0, 85.0],
+			mass: 1.0,
+			restitution: 1.0,
+			filter: CollisionFilter::new(LAYER_BALL, ALL_LAYERS),
+			start: position,
 		}
 	}
 
-	pub fn update(&mut self, mut scene: &mut Scene, dt: f64, objects: &mut Vec<&mut CollidableObject>) {
-		self.object.position[0] += self.v[0] * dt as f32;
-		self.object.position[1] += self.v[1] * dt as f32;
+	pub fn get_id(&self) -> u32 {
+		self.object.get_id()
+	}
+
+	pub fn get_object(&self) -> &GameObject {
+		&self.object
+	}
+
+	pub fn get_shape(&self) -> &CircleObject {
+		&self.shape
+	}
+
+	// Everything the ball can reach within dt, bounces included
+	pub fn get_swept_aabb(&self, dt: f64) -> Aabb {
+		self.shape.get_aabb().expanded(vec2_len(self.v) * dt as f32)
+	}
+
+	// Everything the ball passed through in the last update, in a straight line
+	pub fn get_step_aabb(&self) -> Aabb {
+		Aabb::from_points(&[self.start, self.get_position()]).expanded(self.shape.get_scaled_radius())
+	}
+
+	pub fn get_position(&self) -> Vector2<f32> {
+		[self.object.position[0], self.object.position[1]]
+	}
+
+	// Puts the ball there without it having moved, see get_step_aabb
+	pub fn set_position(&mut self, position: Vector2<f32>) {
+		self.move_to(position);
+		self.start = position;
+	}
+
+	fn move_to(&mut self, position: Vector2<f32>) {
+		self.object.position[0] = position[0];
+		self.object.position[1] = position[1];
 		self.object.update();
-		self.shape.set_position([self.object.position[0], self.object.position[1]]);
-		for object in objects {
-			if let Some((disp, normal)) = get_collision(&self.shape, object.shape.as_ref()) {
-				object.collision_response.on_collision(&mut scene, &disp, &normal);
-
-				//reflect
-				let tmp = vec2_dot(self.v, normal) * 2.0;
-				self.v = vec2_sub(self.v, vec2_mul([tmp, tmp], normal));
-			
-				//displace
-				self.object.position = vec3_add(self.object.position, [disp[0], disp[1], 0.0]);
-				self.object.update();
-				// Note: no need to update shape's position
+		self.shape.set_position(position);
+	}
+
+	pub fn get_velocity(&self) -> Vector2<f32> {
+		self.v
+	}
+
+	pub fn set_velocity(&mut self, v: Vector2<f32>) {
+		self.v = v;
+	}
+
+	pub fn get_mass(&self) -> f32 {
+		self.mass
+	}
+
+	pub fn set_mass(&mut self, mass: f32) {
+		assert!(mass > 0.0);
+		self.mass = mass;
+	}
+
+	pub fn get_restitution(&self) -> f32 {
+		self.restitution
+	}
+
+	pub fn set_restitution(&mut self, restitution: f32) {
+		self.restitution = restitution;
+	}
+
+	pub fn get_filter(&self) -> CollisionFilter {
+		self.filter
+	}
+
+	// Objects and other balls the filter rules out are passed through as if they were not there
+	pub fn set_filter(&mut self, filter: CollisionFilter) {
+		self.filter = filter;
+	}
+
+	// Only objects at the candidates indices are tested, see UniformGrid::query.
+	// Returns the number of collisions resolved
+	pub fn update(&mut self, scene: &mut Scene, events: &mut CollisionEvents, dt: f64, objects: &mut [&mut CollidableObject], candidates: &[usize]) -> u32 {
+		let mut collisions = 0;
+		self.shape.set_scale([self.object.scale[0], self.object.scale[1]]);
+		self.start = self.get_position();
+
+		// Sweep along the velocity so a fast ball stops at the first surface instead of passing through it
+		let mut remaining = dt as f32;
+		let mut triggered = Vec::new();
+		for _ in 0..MAX_SWEEPS {
+			let motion = vec2_scale(self.v, remaining);
+			let mut first: Option<(usize, TimeOfImpact)> = None;
+			let mut triggers = Vec::new();
+			for &i in candidates {
+				let object = &objects[i];
+				if object.is_destroyed() || !self.filter.collides_with(&object.filter) {
+					continue;
+				}
+				if let Some(toi) = get_time_of_impact(&self.shape, motion, object.shape.as_ref(), [0.0, 0.0]) {
+					if !object.is_solid() {
+						triggers.push((i, toi));
+					} else if first.is_none_or(|(_, best)| toi.time < best.time) {
+						first = Some((i, toi));
+					}
+				}
+			}
+
+			let travelled = first.map_or(1.0, |(_, toi)| toi.time);
+			for (i, toi) in triggers {
+				if toi.time <= travelled && !triggered.contains(&i) {
+					triggered.push(i);
+					let position = vec2_add(self.get_position(), vec2_scale(motion, toi.time));
+					self.on_hit(objects[i], scene, events, position, &[0.0, 0.0], &toi.normal);
+				}
+			}
+
+			match first {
+				Some((i, toi)) => {
+					// Stop just short of the surface so the overlap test below does not find it again
+					let time = max(toi.time - SWEEP_SKIN / vec2_len(motion), 0.0);
+					self.translate(vec2_scale(motion, time));
+					let position = self.get_position();
+					self.on_hit(objects[i], scene, events, position, &[0.0, 0.0], &toi.normal);
+					self.bounce(objects[i], toi.normal);
+					remaining *= 1.0 - time;
+					collisions += 1;
+				}
+				None => {
+					self.translate(motion);
+					break;
+				}
 			}
 		}
+
+		// Overlaps the sweep can not see, like the paddle moving into the ball
+		for &i in candidates {
+			let object = &mut objects[i];
+			if object.is_destroyed() || triggered.contains(&i) || !self.filter.collides_with(&object.filter) {
+				continue;
+			}
+			if let Some((disp, normal)) = get_collision_with(&self.shape, object.shape.as_ref(), object.narrow_phase) {
+				let position = self.get_position();
+				self.on_hit(object, scene, events, position, &disp, &normal);
+				if !object.is_solid() {
+					continue;
+				}
+
+				self.bounce(object, normal);
+				self.translate(disp);
+				collisions += 1;
+			}
+		}
+		collisions
+	}
+
+	fn translate(&mut self, offset: Vector2<f32>) {
+		let position = self.get_position();
+		self.move_to(vec2_add(position, offset));
+	}
+
+	// Only when moving into the surface, so touching it twice does not send the ball back in
+	fn bounce(&mut self, object: &CollidableObject, normal: Vector2<f32>) {
+		if vec2_dot(self.v, normal) < 0.0 {
+			self.v = object.bounce(self.get_position(), self.v, normal);
+		}
+	}
+
+	// position is where the center of the ball was when it touched the object
+	fn on_hit(&mut self, object: &mut CollidableObject, scene: &mut Scene, events: &mut CollisionEvents, position: Vector2<f32>, disp: &Vector2<f32>, normal: &Vector2<f32>) {
+		object.on_collision(scene, disp, normal);
+		events.add_contact(Contact {
+			a: self.get_id(),
+			a_type: Participant::Ball,
+			b: object.object.get_id(),
+			b_type: Participant::Object(object.get_kind()),
+			point: vec2_sub(position, vec2_scale(*normal, self.shape.get_scaled_radius())),
+			normal: *normal,
+			relative_velocity: vec2_sub(self.v, object.velocity),
+			destroyed_for_points: if object.is_destroyed() { Some(object.get_points()) } else { None },
+		});
 	}
 
-	pub fn collide_with_other_balls(&mut self, balls: &[BallObject]) {
-		for ball in balls {
-			if let Some((disp, normal)) = get_collision(&self.shape, &ball.shape) {
-				//reflect
-				let tmp = vec2_dot(self.v, normal) * 2.0;
-				self.v = vec2_sub(self.v, vec2_mul([tmp, tmp], normal));
-			
-				//displace
-				self.object.position = vec3_add(self.object.position, [disp[0], disp[1], 0.0]);
-				self.object.update();
-				// Note: no need to update shape's position
+	// Resolves an overlap with another ball, both balls are pushed apart and exchange momentum.
+	// Balls that passed through each other during the last update are moved back to where they
+	// touched. Returns the contact when they collided
+	pub fn collide_with_ball(&mut self, other: &mut BallObject) -> Option<Contact> {
+		if !self.filter.collides_with(&other.filter) {
+			return None;
+		}
+		let relative_velocity = vec2_sub(self.v, other.v);
+		let inverse_mass = 1.0 / self.mass;
+		let other_inverse_mass = 1.0 / other.mass;
+		let normal = match get_collision(&self.shape, &other.shape) {
+			Some((disp, normal)) => {
+				// The lighter ball is pushed further
+				let share = inverse_mass / (inverse_mass + other_inverse_mass);
+				self.translate(vec2_scale(disp, share));
+				other.translate(vec2_scale(disp, share - 1.0));
+				normal
+			}
+			None => {
+				// Time left after touching is dropped, like for sweeps against objects
+				let toi = get_time_of_impact_circles(&self.get_start_shape(), self.get_step_motion(), &other.get_start_shape(), other.get_step_motion())?;
+				let position = vec2_add(self.start, vec2_scale(self.get_step_motion(), toi.time));
+				let other_position = vec2_add(other.start, vec2_scale(other.get_step_motion(), toi.time));
+				self.move_to(position);
+				other.move_to(other_position);
+				toi.normal
 			}
+		};
+
+		// Impulse along the normal, only when they move towards each other
+		let speed = vec2_dot(vec2_sub(self.v, other.v), normal);
+		if speed < 0.0 {
+			let restitution = min(self.restitution, other.restitution);
+			let impulse = -(1.0 + restitution) * speed / (inverse_mass + other_inverse_mass);
+			self.v = vec2_add(self.v, vec2_scale(normal, impulse * inverse_mass));
+			other.v = vec2_sub(other.v, vec2_scale(normal, impulse * other_inverse_mass));
 		}
+		Some(Contact {
+			a: self.get_id(),
+			a_type: Participant::Ball,
+			b: other.get_id(),
+			b_type: Participant::Ball,
+			point: vec2_sub(self.get_position(), vec2_scale(normal, self.shape.get_scaled_radius())),
+			normal,
+			relative_velocity,
+			destroyed_for_points: None,
+		})
 	}
+
+	fn get_step_motion(&self) -> Vector2<f32> {
+		vec2_sub(self.get_position(), self.start)
+	}
+
+	fn get_start_shape(&self) -> CircleObject {
+		let mut shape = CircleObject::new(self.start, self.shape.radius);
+		shape.scale = self.shape.scale;
+		shape
+	}
+}
+
+fn clamp_vertical_speed(velocity: Vector2<f32>) -> Vector2<f32> {
+	let speed = vec2_len(velocity);
+	let min_vertical = speed * MIN_VERTICAL_SPEED;
+	if velocity[1].abs() >= min_vertical {
+		return velocity;
+	}
+	let vertical = if velocity[1] < 0.0 { -min_vertical } else { min_vertical };
+	let horizontal = (speed * speed - min_vertical * min_vertical).sqrt();
+	[if velocity[0] < 0.0 { -horizontal } else { horizontal }, vertical]
 }
 
+// Where the ball lands on top of the paddle sets the angle it leaves at, like in breakout
 pub struct PlayerCollision {
 }
 
 impl Collidable for PlayerCollision {
-	fn on_collision(&mut self, scene: &mut Scene, disp: &Vector2<f32>,  normal: &Vector2<f32>) {
+	fn bounce(&self, object: &GameObject, object_velocity: Vector2<f32>, position: Vector2<f32>, velocity: Vector2<f32>, normal: Vector2<f32>) -> Vector2<f32> {
+		// The sides and the bottom bounce like any other box
+		let speed = vec2_len(velocity);
+		if normal[1] <= 0.5 || speed == 0.0 {
+			return reflect(velocity, normal);
+		}
+		// The paddle is a unit box scaled to size
+		let half_width = object.scale[0] / 2.0;
+		let offset = min(max((position[0] - object.position[0]) / half_width, -1.0), 1.0);
+		let angle = offset * MAX_BOUNCE_ANGLE;
+		let aimed = [speed * angle.sin() + object_velocity[0] * PADDLE_VELOCITY_INFLUENCE, speed * angle.cos()];
+		// Moving the paddle changes the direction, not the speed
+		clamp_vertical_speed(vec2_scale(vec2_normalized(aimed), speed))
+	}
 
+	fn get_kind(&self) -> CollidableKind {
+		CollidableKind::Player
 	}
 }
 
@@ -312,7 +913,246 @@ pub struct WallCollision {
 }
 
 impl Collidable for WallCollision {
-	fn on_collision(&mut self, scene: &mut Scene, disp: &Vector2<f32>,  normal: &Vector2<f32>) {
+	fn get_kind(&self) -> CollidableKind {
+		CollidableKind::Wall
+	}
+}
+
+// Balls entering it are lost
+pub struct OutOfBoundsCollision {
+}
+
+impl Collidable for OutOfBoundsCollision {
+	fn get_kind(&self) -> CollidableKind {
+		CollidableKind::OutOfBounds
+	}
+
+	fn is_solid(&self) -> bool {
+		false
+	}
+}
+
+pub struct Brick {
+	hit_points: u32,
+	points: u32,
+	// Indexed by remaining hit points - 1, the last one is used for anything above
+	materials: Vec<Material>,
+}
+
+impl Brick {
+	pub fn new(hit_points: u32, points: u32, materials: &[Material]) -> Self {
+		assert!(hit_points > 0 && !materials.is_empty());
+		Brick {
+			hit_points,
+			points,
+			materials: materials.to_vec(),
+		}
+	}
+
+	pub fn get_hit_points(&self) -> u32 {
+		self.hit_points
+	}
+
+	pub fn get_material(&self) -> &Material {
+		let index = min(self.hit_points.saturating_sub(1) as usize, self.materials.len() - 1);
+		&self.materials[index]
+	}
+}
+
+impl Collidable for Brick {
+	fn on_collision(&mut self, object: &mut GameObject, scene: &mut Scene, _disp: &Vector2<f32>, _normal: &Vector2<f32>) {
+		if self.hit_points == 0 {
+			return;
+		}
+		self.hit_points -= 1;
+		if self.hit_points == 0 {
+			scene.remove(object);
+		} else {
+			let material = *self.get_material();
+			scene.set_material(object, &material);
+		}
+	}
+
+	fn get_kind(&self) -> CollidableKind {
+		CollidableKind::Brick
+	}
+
+	fn is_destroyed(&self) -> bool {
+		self.hit_points == 0
+	}
 
+	fn get_points(&self) -> u32 {
+		self.points
 	}
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_brick_hit_points() {
+		let materials = Materials::new();
+		let geometries = Geometries::new();
+		let mut scene = Scene::new();
+		let mut brick = Brick::new(2, 20, &materials.bricks);
+		let mut object = GameObject::new(7, &geometries.unit_box_geometry, brick.get_material(), false, true);
+		assert_eq!(object.get_material_id(), materials.bricks[1].get_id());
+		scene.add(&mut object);
+		scene.take_changes();
+
+		brick.on_collision(&mut object, &mut scene, &[0.0, 1.0], &[0.0, 1.0]);
+		assert_eq!(brick.get_hit_points(), 1);
+		assert!(!brick.is_destroyed());
+		assert_eq!(object.get_material_id(), materials.bricks[0].get_id());
+		assert_eq!(scene.take_changes(), vec![SceneChange::SetMaterial { id: 7, material: materials.bricks[0].get_id() }]);
+
+		brick.on_collision(&mut object, &mut scene, &[0.0, 1.0], &[0.0, 1.0]);
+		assert!(brick.is_destroyed());
+		assert!(!scene.contains(&object));
+		assert_eq!(scene.take_changes(), vec![SceneChange::Remove(7)]);
+
+		// Already destroyed, nothing left to do
+		brick.on_collision(&mut object, &mut scene, &[0.0, 1.0], &[0.0, 1.0]);
+		assert!(scene.take_changes().is_empty());
+	}
+
+	fn ball(id: u32, position: Vector2<f32>, v: Vector2<f32>, mass: f32) -> BallObject {
+		let geometries = Geometries::new();
+		let materials = Materials::new();
+		let mut ball = BallObject::new(GameObject::new(id, &geometries.sphere_geometry, &materials.ball, false, false), 1.0);
+		ball.set_position(position);
+		ball.set_velocity(v);
+		ball.set_mass(mass);
+		ball
+	}
+
+	fn momentum(a: &BallObject, b: &BallObject) -> Vector2<f32> {
+		vec2_add(vec2_scale(a.get_velocity(), a.get_mass()), vec2_scale(b.get_velocity(), b.get_mass()))
+	}
+
+	#[test]
+	fn test_ball_collision_equal_mass() {
+		let mut a = ball(0, [0.0, 0.0], [10.0, 0.0], 1.0);
+		let mut b = ball(1, [1.5, 0.0], [-5.0, 0.0], 1.0);
+		assert!(a.collide_with_ball(&mut b).is_some());
+		// Head on with equal masses the velocities are swapped
+		assert_eq!(a.get_velocity(), [-5.0, 0.0]);
+		assert_eq!(b.get_velocity(), [10.0, 0.0]);
+		// Each is pushed back by half of the overlap
+		assert_eq!(a.get_position(), [-0.25, 0.0]);
+		assert_eq!(b.get_position(), [1.75, 0.0]);
+		assert!(a.collide_with_ball(&mut b).is_none());
+	}
+
+	#[test]
+	fn test_ball_collision_momentum() {
+		let mut a = ball(0, [0.0, 0.0], [10.0, 3.0], 3.0);
+		let mut b = ball(1, [1.2, 1.0], [-4.0, 1.0], 1.0);
+		let before = momentum(&a, &b);
+		let energy = |a: &BallObject, b: &BallObject| a.get_mass() * vec2_square_len(a.get_velocity()) + b.get_mass() * vec2_square_len(b.get_velocity());
+		let energy_before = energy(&a, &b);
+		assert!(a.collide_with_ball(&mut b).is_some());
+		let after = momentum(&a, &b);
+		assert!(vec2_len(vec2_sub(before, after)) < 0.0001);
+		assert!((energy_before - energy(&a, &b)).abs() < 0.01);
+
+		// The heavy ball moves less
+		let a_moved = vec2_len(vec2_sub(a.get_position(), [0.0, 0.0]));
+		let b_moved = vec2_len(vec2_sub(b.get_position(), [1.2, 1.0]));
+		assert!((b_moved - 3.0 * a_moved).abs() < 0.0001);
+	}
+
+	#[test]
+	fn test_ball_collision_restitution() {
+		let mut a = ball(0, [0.0, 0.0], [10.0, 0.0], 1.0);
+		let mut b = ball(1, [1.5, 0.0], [0.0, 0.0], 1.0);
+		b.set_restitution(0.0);
+		assert!(a.collide_with_ball(&mut b).is_some());
+		// Nothing bounces back, both move on together
+		assert_eq!(a.get_velocity(), [5.0, 0.0]);
+		assert_eq!(b.get_velocity(), [5.0, 0.0]);
+	}
+
+	#[test]
+	fn test_separating_balls_keep_velocity() {
+		let mut a = ball(0, [0.0, 0.0], [-10.0, 0.0], 1.0);
+		let mut b = ball(1, [1.5, 0.0], [10.0, 0.0], 1.0);
+		assert!(a.collide_with_ball(&mut b).is_some());
+		assert_eq!(a.get_velocity(), [-10.0, 0.0]);
+		assert_eq!(b.get_velocity(), [10.0, 0.0]);
+	}
+
+	#[test]
+	fn test_collision_filter() {
+		let ball_filter = CollisionFilter::new(LAYER_BALL, ALL_LAYERS & !LAYER_BRICK);
+		assert!(!ball_filter.collides_with(&CollisionFilter::new(LAYER_BRICK, ALL_LAYERS)));
+		assert!(ball_filter.collides_with(&CollisionFilter::new(LAYER_WALL, ALL_LAYERS)));
+		// Both sides have to agree
+		assert!(!ball_filter.collides_with(&CollisionFilter::new(LAYER_WALL, LAYER_PLAYER)));
+
+		let mut a = ball(0, [0.0, 0.0], [10.0, 0.0], 1.0);
+		let mut b = ball(1, [1.5, 0.0], [-5.0, 0.0], 1.0);
+		b.set_filter(CollisionFilter::new(LAYER_BALL, ALL_LAYERS & !LAYER_BALL));
+		assert!(a.collide_with_ball(&mut b).is_none());
+		assert_eq!(a.get_velocity(), [10.0, 0.0]);
+		assert_eq!(b.get_position(), [1.5, 0.0]);
+	}
+
+	fn paddle() -> GameObject {
+		let mut object = GameObject::new(0, &Geometries::new().unit_box_geometry, &Materials::new().player, false, false);
+		object.position = [0.0, -40.0, 2.5];
+		object.scale = [20.0, 3.0, 8.0];
+		object
+	}
+
+	#[test]
+	fn test_paddle_bounce_angle() {
+		let paddle = paddle();
+		let response = PlayerCollision {};
+		let bounce = |x: f32, velocity: Vector2<f32>| response.bounce(&paddle, [0.0, 0.0], [x, -37.0], [0.0, -10.0], velocity);
+
+		let v = bounce(0.0, [0.0, 1.0]);
+		assert!(vec2_len(vec2_sub(v, [0.0, 10.0])) < 0.0001);
+
+		// The very end sends it off at the max angle, further out is clamped
+		let end = [10.0 * MAX_BOUNCE_ANGLE.sin(), 10.0 * MAX_BOUNCE_ANGLE.cos()];
+		assert!(vec2_len(vec2_sub(bounce(10.0, [0.0, 1.0]), end)) < 0.0001);
+		assert!(vec2_len(vec2_sub(bounce(12.0, [0.0, 1.0]), end)) < 0.0001);
+		assert!(bounce(-5.0, [0.0, 1.0])[0] < 0.0);
+
+		// Sides are a plain reflection
+		assert_eq!(response.bounce(&paddle, [0.0, 0.0], [11.0, -40.0], [-10.0, 0.0], [1.0, 0.0]), [10.0, 0.0]);
+	}
+
+	#[test]
+	fn test_paddle_velocity_bounce() {
+		let paddle = paddle();
+		let response = PlayerCollision {};
+		let v = response.bounce(&paddle, [50.0, 0.0], [0.0, -37.0], [0.0, -10.0], [0.0, 1.0]);
+		assert!(v[0] > 0.0 && v[1] > 0.0);
+		assert!((vec2_len(v) - 10.0).abs() < 0.0001);
+
+		// However fast the paddle moves, the ball still goes up
+		let v = response.bounce(&paddle, [5000.0, 0.0], [9.0, -37.0], [0.0, -10.0], [0.0, 1.0]);
+		assert!((v[1] - 10.0 * MIN_VERTICAL_SPEED).abs() < 0.0001);
+	}
+
+	#[test]
+	fn test_clamp_vertical_speed() {
+		let v = clamp_vertical_speed([-10.0, 0.1]);
+		let speed = vec2_len([-10.0, 0.1]);
+		assert!((vec2_len(v) - speed).abs() < 0.0001);
+		assert!((v[1] - speed * MIN_VERTICAL_SPEED).abs() < 0.0001);
+		assert!(v[0] < 0.0);
+
+		assert_eq!(clamp_vertical_speed([3.0, -4.0]), [3.0, -4.0]);
+	}
+
+	#[test]
+	fn test_brick_material_above_known_hit_points() {
+		let materials = Materials::new();
+		let brick = Brick::new(10, 100, &materials.bricks);
+		assert_eq!(brick.get_material(), materials.bricks.last().unwrap());
+	}
+}
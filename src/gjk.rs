@@ -0,0 +1,405 @@
+use vecmath::*;
+
+use sat::{CollistionInfo, Shape, ShapeDistance};
+
+const MAX_ITERATIONS: usize = 32;
+// Getting less than this much closer to the origin ends the search
+const TOLERANCE: f32 = 0.00001;
+
+// Point of the Minkowski difference a - b, with the points of a and b it was made from
+#[derive(Debug, Clone, Copy)]
+struct SupportPoint {
+	point: Vector2<f32>,
+	a: Vector2<f32>,
+	b: Vector2<f32>,
+}
+
+// Furthest point of the Minkowski difference a - b along direction, with a moved by offset
+fn support(a: &dyn Shape, b: &dyn Shape, offset: Vector2<f32>, direction: Vector2<f32>) -> SupportPoint {
+	let a = vec2_add(a.get_support(direction), offset);
+	let b = b.get_support(vec2_neg(direction));
+	SupportPoint { point: vec2_sub(a, b), a, b }
+}
+
+// How far along p to q the point closest to the origin is, from 0 to 1
+fn closest_along(p: Vector2<f32>, q: Vector2<f32>) -> f32 {
+	let edge = vec2_sub(q, p);
+	let length = vec2_square_len(edge);
+	if length == 0.0 {
+		return 1.0;
+	}
+	vec2_dot(vec2_neg(p), edge) / length
+}
+
+// The points of a and b making up the point of the edge closest to the origin
+fn edge_witnesses(p: SupportPoint, q: SupportPoint) -> [Vector2<f32>; 2] {
+	let along = closest_along(p.point, q.point).clamp(0.0, 1.0);
+	let lerp = |from: Vector2<f32>, to: Vector2<f32>| vec2_add(from, vec2_scale(vec2_sub(to, from), along));
+	[lerp(p.a, q.a), lerp(p.b, q.b)]
+}
+
+// Up to a triangle, no allocation
+#[derive(Debug, Clone, Copy)]
+struct Simplex {
+	points: [SupportPoint; 3],
+	count: usize,
+}
+
+impl Simplex {
+	fn new(first: SupportPoint) -> Self {
+		Simplex { points: [first; 3], count: 1 }
+	}
+
+	fn push(&mut self, point: SupportPoint) {
+		self.points[self.count] = point;
+		self.count += 1;
+	}
+
+	// Closest point to the origin, keeping only the points of the feature it lies on
+	fn reduce(&mut self) -> Vector2<f32> {
+		match self.count {
+			1 => self.points[0].point,
+			2 => self.reduce_segment(0, 1),
+			_ => {
+				let [a, b, c] = [self.points[0].point, self.points[1].point, self.points[2].point];
+				if contains_origin(a, b, c) {
+					return [0.0, 0.0];
+				}
+				let mut best = *self;
+				let mut best_closest = [f32::MAX, f32::MAX];
+				for &(i, j) in &[(0, 1), (1, 2), (2, 0)] {
+					let mut edge = *self;
+					let closest = edge.reduce_segment(i, j);
+					if vec2_square_len(closest) < vec2_square_len(best_closest) {
+						best = edge;
+						best_closest = closest;
+					}
+				}
+				*self = best;
+				best_closest
+			}
+		}
+	}
+
+	fn reduce_segment(&mut self, i: usize, j: usize) -> Vector2<f32> {
+		let (p, q) = (self.points[i], self.points[j]);
+		let along = closest_along(p.point, q.point);
+		if along <= 0.0 {
+			*self = Simplex::new(p);
+			p.point
+		} else if along >= 1.0 {
+			*self = Simplex::new(q);
+			q.point
+		} else {
+			self.points[0] = p;
+			self.points[1] = q;
+			self.count = 2;
+			vec2_add(p.point, vec2_scale(vec2_sub(q.point, p.point), along))
+		}
+	}
+
+	// Nearest points of a and b, for a simplex reduced to a point or an edge
+	fn witnesses(&self) -> [Vector2<f32>; 2] {
+		match self.count {
+			1 => [self.points[0].a, self.points[0].b],
+			_ => edge_witnesses(self.points[0], self.points[1]),
+		}
+	}
+}
+
+enum Gjk {
+	// Direction from b towards a, the distance between them and their nearest points
+	Separated(Vector2<f32>, f32, [Vector2<f32>; 2]),
+	// The origin is inside the triangle, or on a point or edge when count is lower
+	Overlapping(Simplex),
+}
+
+// Distance between the cores of two convex shapes, the radius is not included
+fn gjk(a: &dyn Shape, b: &dyn Shape, offset: Vector2<f32>) -> Gjk {
+	// Any direction works as long as it is not zero, which would not give a point on the boundary
+	let mut direction = vec2_sub(vec2_add(a.get_position(), offset), b.get_position());
+	if vec2_square_len(direction) == 0.0 {
+		direction = [1.0, 0.0];
+	}
+	let mut simplex = Simplex::new(support(a, b, offset, direction));
+	let mut closest = simplex.reduce();
+	for _ in 0..MAX_ITERATIONS {
+		if vec2_square_len(closest) < TOLERANCE * TOLERANCE {
+			return Gjk::Overlapping(simplex);
+		}
+		let next = support(a, b, offset, vec2_neg(closest));
+		// Nothing closer to the origin than the current closest point
+		if vec2_square_len(closest) - vec2_dot(closest, next.point) <= TOLERANCE * vec2_len(closest) {
+			break;
+		}
+		simplex.push(next);
+		closest = simplex.reduce();
+	}
+	separation(&simplex, closest)
+}
+
+// When the cores nearly touch, the closest point is too short to normalize precisely.
+// The edge it lies on gives the direction instead
+fn separation(simplex: &Simplex, closest: Vector2<f32>) -> Gjk {
+	let witnesses = simplex.witnesses();
+	if simplex.count == 2 {
+		let edge = vec2_sub(simplex.points[1].point, simplex.points[0].point);
+		let mut normal = vec2_normalized([-edge[1], edge[0]]);
+		if vec2_dot(normal, closest) < 0.0 {
+			normal = vec2_neg(normal);
+		}
+		return Gjk::Separated(normal, vec2_dot(normal, simplex.points[0].point), witnesses);
+	}
+	let distance = vec2_len(closest);
+	Gjk::Separated(vec2_scale(closest, 1.0 / distance), distance, witnesses)
+}
+
+// Does not count touching
+pub fn gjk_intersects(a: &dyn Shape, b: &dyn Shape) -> bool {
+	get_collision_gjk(a, b).is_some()
+}
+
+// Same result as sat::get_collision for two convex shapes, found with GJK and EPA
+pub fn get_collision_gjk(a: &dyn Shape, b: &dyn Shape) -> CollistionInfo {
+	let result = with_radii(a, b, core_distance(a, b, [0.0, 0.0]));
+	if -result.distance < TOLERANCE {
+		return None;
+	}
+	Some((vec2_scale(result.normal, -result.distance), result.normal))
+}
+
+// Same as sat::get_distance for two convex shapes
+pub fn get_distance_gjk(a: &dyn Shape, b: &dyn Shape) -> ShapeDistance {
+	with_radii(a, b, core_distance(a, b, [0.0, 0.0]))
+}
+
+// Signed distance from a moved by offset to b, for trying a move without changing a
+pub fn get_moved_distance_gjk(a: &dyn Shape, b: &dyn Shape, offset: Vector2<f32>) -> f32 {
+	let (_, distance, _) = core_distance(a, b, offset);
+	distance - a.get_radius() - b.get_radius()
+}
+
+// Signed distance between the cores with the direction from b to a and the nearest points of each
+fn core_distance(a: &dyn Shape, b: &dyn Shape, offset: Vector2<f32>) -> (Vector2<f32>, f32, [Vector2<f32>; 2]) {
+	match gjk(a, b, offset) {
+		Gjk::Separated(normal, distance, witnesses) => (normal, distance, witnesses),
+		Gjk::Overlapping(simplex) => match expand(a, b, offset, simplex) {
+			Some(triangle) => {
+				let (normal, distance, witnesses) = epa(a, b, offset, triangle);
+				(vec2_neg(normal), -distance, witnesses)
+			}
+			// Both cores are flat and on one line, only rounding can make them overlap.
+			// When they share a center as well, any direction is as good as another
+			None => {
+				let direction = vec2_sub(vec2_add(a.get_position(), offset), b.get_position());
+				let normal = if vec2_square_len(direction) == 0.0 { [1.0, 0.0] } else { vec2_normalized(direction) };
+				(normal, 0.0, [vec2_add(a.get_support(vec2_neg(normal)), offset), b.get_support(normal)])
+			}
+		},
+	}
+}
+
+fn with_radii(a: &dyn Shape, b: &dyn Shape, core: (Vector2<f32>, f32, [Vector2<f32>; 2])) -> ShapeDistance {
+	let (normal, distance, [point_a, point_b]) = core;
+	ShapeDistance {
+		distance: distance - a.get_radius() - b.get_radius(),
+		point_a: vec2_sub(point_a, vec2_scale(normal, a.get_radius())),
+		point_b: vec2_add(point_b, vec2_scale(normal, b.get_radius())),
+		normal,
+	}
+}
+
+// Grows the simplex around the origin into a triangle for EPA
+fn expand(a: &dyn Shape, b: &dyn Shape, offset: Vector2<f32>, mut simplex: Simplex) -> Option<[SupportPoint; 3]> {
+	let directions = [[1.0, 0.0], [-1.0, 0.0], [0.0, 1.0], [0.0, -1.0]];
+	if simplex.count == 1 {
+		let first = simplex.points[0].point;
+		let next = directions.iter()
+			.map(|direction| support(a, b, offset, *direction))
+			.find(|next| vec2_square_len(vec2_sub(next.point, first)) > TOLERANCE * TOLERANCE)?;
+		simplex.push(next);
+	}
+	if simplex.count == 2 {
+		let (p, q) = (simplex.points[0].point, simplex.points[1].point);
+		let edge = vec2_sub(q, p);
+		let normal = [-edge[1], edge[0]];
+		let next = [normal, vec2_neg(normal)].iter()
+			.map(|direction| support(a, b, offset, *direction))
+			.find(|next| cross(edge, vec2_sub(next.point, p)).abs() > TOLERANCE * vec2_len(edge))?;
+		simplex.push(next);
+	}
+	let [p, q, r] = simplex.points;
+	// Counter clock wise
+	if cross(vec2_sub(q.point, p.point), vec2_sub(r.point, p.point)) < 0.0 {
+		Some([p, r, q])
+	} else {
+		Some([p, q, r])
+	}
+}
+
+// Expanding polytope: pushes the edge of a - b closest to the origin outwards until it is on
+// the boundary. Returns its outward normal, its distance from the origin and the points of a
+// and b making up its closest point
+fn epa(a: &dyn Shape, b: &dyn Shape, offset: Vector2<f32>, triangle: [SupportPoint; 3]) -> (Vector2<f32>, f32, [Vector2<f32>; 2]) {
+	// Grows by one point per iteration, so it fits on the stack
+	let mut polytope = [triangle[0]; 3 + MAX_ITERATIONS];
+	polytope[..3].copy_from_slice(&triangle);
+	let mut count = 3;
+	let mut closest = closest_edge(&polytope[..count]);
+	while count < polytope.len() {
+		let (index, normal, distance) = closest;
+		let next = support(a, b, offset, normal);
+		if vec2_dot(next.point, normal) - distance < TOLERANCE {
+			break;
+		}
+		polytope.copy_within(index + 1..count, index + 2);
+		polytope[index + 1] = next;
+		count += 1;
+		closest = closest_edge(&polytope[..count]);
+	}
+	let (index, normal, distance) = closest;
+	(normal, distance, edge_witnesses(polytope[index], polytope[(index + 1) % count]))
+}
+
+fn closest_edge(polytope: &[SupportPoint]) -> (usize, Vector2<f32>, f32) {
+	let mut result = (0, [0.0, 0.0], f32::MAX);
+	for i in 0..polytope.len() {
+		let p = polytope[i].point;
+		let edge = vec2_sub(polytope[(i + 1) % polytope.len()].point, p);
+		if vec2_square_len(edge) == 0.0 {
+			continue;
+		}
+		// Outwards for counter clock wise winding
+		let normal = vec2_normalized([edge[1], -edge[0]]);
+		let distance = vec2_dot(normal, p);
+		if distance < result.2 {
+			result = (i, normal, distance);
+		}
+	}
+	result
+}
+
+fn contains_origin(a: Vector2<f32>, b: Vector2<f32>, c: Vector2<f32>) -> bool {
+	let area = cross(vec2_sub(b, a), vec2_sub(c, a));
+	if area == 0.0 {
+		return false;
+	}
+	let sides = [
+		cross(vec2_sub(b, a), vec2_neg(a)),
+		cross(vec2_sub(c, b), vec2_neg(b)),
+		cross(vec2_sub(a, c), vec2_neg(c)),
+	];
+	sides.iter().all(|side| side * area >= 0.0)
+}
+
+fn cross(a: Vector2<f32>, b: Vector2<f32>) -> f32 {
+	a[0] * b[1] - a[1] * b[0]
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use sat::*;
+	use std::f32::consts::PI;
+
+	fn regular_polygon(position: Vector2<f32>, sides: usize, radius: f32) -> ConvexObject {
+		let verts = (0..sides).map(|i| {
+			let angle = -(i as f32) * 2.0 * PI / sides as f32;
+			[angle.cos() * radius, angle.sin() * radius]
+		}).collect();
+		ConvexObject::new(position, verts)
+	}
+
+	fn make_shape(index: usize, position: Vector2<f32>) -> Box<dyn Shape> {
+		match index {
+			0 => Box::new(ConvexObject::new(position, vec![[-1.0, -1.0], [0.0, 1.0], [1.0, -1.0]])),
+			1 => Box::new(regular_polygon(position, 5, 1.2)),
+			2 => Box::new(CircleObject::new(position, 1.0)),
+			3 => Box::new(CapsuleObject::new(position, 1.0, 0.5)),
+			4 => Box::new(SegmentObject::new(vec2_add(position, [-1.0, -0.5]), vec2_add(position, [1.0, 0.5]))),
+			5 => Box::new(AabbObject::new(position, [1.0, 0.6])),
+			6 => Box::new(ChainObject::new(position, vec![[-1.5, -1.0], [0.0, 1.0], [1.5, -1.0]], false)),
+			_ => Box::new(CompoundObject::new(position, &[[-1.0, -1.0], [1.0, -1.0], [1.0, 0.0], [0.0, 0.0], [0.0, 1.0], [-1.0, 1.0]]).unwrap()),
+		}
+	}
+
+	// Same numbers on every run
+	struct Random(u32);
+
+	impl Random {
+		fn next(&mut self) -> f32 {
+			self.0 = self.0.wrapping_mul(1664525).wrapping_add(1013904223);
+			(self.0 >> 8) as f32 / (1 << 24) as f32
+		}
+	}
+
+	fn eq_eps(a: Vector2<f32>, b: Vector2<f32>, eps: f32) {
+		assert!((a[0] - b[0]).abs() < eps, "{:?} != {:?}", a, b);
+		assert!((a[1] - b[1]).abs() < eps, "{:?} != {:?}", a, b);
+	}
+
+	#[test]
+	fn test_gjk_boxes() {
+		let verts = vec![[-1.0, 1.0], [1.0, 1.0], [1.0, -1.0], [-1.0, -1.0]];
+		let obj_a = ConvexObject::new([0.0, 0.0], verts.clone());
+		let obj_b = ConvexObject::new([1.0, 0.0], verts.clone());
+		eq_eps(get_collision_gjk(&obj_a, &obj_b).unwrap().0, [-1.0, 0.0], 0.00001);
+		eq_eps(get_collision_gjk(&obj_b, &obj_a).unwrap().0, [1.0, 0.0], 0.00001);
+
+		let obj_c = ConvexObject::new([3.0, 0.0], verts.clone());
+		assert!(!gjk_intersects(&obj_a, &obj_c));
+		// Touching is not intersecting
+		let obj_d = ConvexObject::new([2.0, 0.0], verts);
+		assert!(!gjk_intersects(&obj_a, &obj_d));
+	}
+
+	#[test]
+	fn test_gjk_circles() {
+		let obj_a = CircleObject::new([0.0, 0.0], 3.0);
+		let obj_b = CircleObject::new([3.0, 3.0], 2.0);
+		eq_eps(get_collision_gjk(&obj_a, &obj_b).unwrap().0, [-0.5355338, -0.5355338], 0.00001);
+
+		// Same center, any way out is as good as another
+		let obj_c = CircleObject::new([0.0, 0.0], 1.0);
+		let (disp, _) = get_collision_gjk(&obj_a, &obj_c).unwrap();
+		assert!((vec2_len(disp) - 4.0).abs() < 0.00001);
+		assert_eq!(get_collision(&obj_a, &obj_c).map(|(disp, _)| vec2_len(disp)), Some(4.0));
+		assert_eq!(get_distance(&obj_a, &obj_c).distance, -4.0);
+
+		// Many sided polygons cost no more axes
+		let obj_d = regular_polygon([0.0, 0.0], 64, 2.0);
+		let obj_e = CircleObject::new([2.5, 0.0], 1.0);
+		let (disp, _) = get_collision_gjk(&obj_d, &obj_e).unwrap();
+		eq_eps(disp, [-0.5, 0.0], 0.00001);
+		eq_eps(disp, get_collision(&obj_d, &obj_e).unwrap().0, 0.00001);
+	}
+
+	#[test]
+	fn test_gjk_matches_sat() {
+		let mut random = Random(7);
+		let mut collisions = 0;
+		for _ in 0..2000 {
+			let a_index = (random.next() * 8.0) as usize;
+			let b_index = (random.next() * 8.0) as usize;
+			let mut a = make_shape(a_index, [0.0, 0.0]);
+			let mut b = make_shape(b_index, [random.next() * 5.0 - 2.5, random.next() * 5.0 - 2.5]);
+			a.set_rotation(random.next() * 2.0 * PI);
+			b.set_rotation(random.next() * 2.0 * PI);
+
+			let sat = get_collision_with(&*a, &*b, NarrowPhase::Sat);
+			let gjk = get_collision_with(&*a, &*b, NarrowPhase::Gjk);
+			match (sat, gjk) {
+				(Some(sat), Some(gjk)) => {
+					eq_eps(sat.0, gjk.0, 0.001);
+					collisions += 1;
+				}
+				// Barely touching, one may still count it
+				(Some((disp, _)), None) | (None, Some((disp, _))) => {
+					assert!(vec2_len(disp) < 0.001, "{} {} {:?} {:?}", a_index, b_index, sat, gjk);
+				}
+				(None, None) => {}
+			}
+		}
+		assert!(collisions > 500);
+	}
+}
@@ -0,0 +1,510 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+
+use serde_json;
+use vecmath::*;
+
+use game::*;
+use sat::*;
+use score::DEFAULT_PAR_TIME;
+
+// Built in levels, in the order they are played
+pub const LEVELS: [&str; 2] = [
+	include_str!("../levels/level1.json"),
+	include_str!("../levels/level2.json"),
+];
+pub const DEFAULT_LEVEL: &str = LEVELS[0];
+
+// Radius of the sphere geometry in code.js, balls of other sizes are scaled
+const BALL_GEOMETRY_RADIUS: f32 = 2.5;
+const DEFAULT_BALL_RADIUS: f32 = 2.5;
+pub const DEFAULT_BALL_MASS: f32 = 1.0;
+pub const DEFAULT_BALL_RESTITUTION: f32 = 1.0;
+
+const WALL_DEPTH: f32 = 10.0;
+const PADDLE_DEPTH: f32 = 8.0;
+const BRICK_DEPTH: f32 = 6.0;
+const BALL_Z: f32 = 5.0;
+const BOX_Z: f32 = 2.5;
+
+const POINTS_PER_HIT_POINT: u32 = 10;
+
+// Penetration below this is treated as touching
+const OVERLAP_TOLERANCE: f32 = 0.001;
+
+// Level file layout, see levels/level1.json
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LevelFile {
+	name: String,
+	arena: Vector2<f32>,
+	walls: Vec<BoxFile>,
+	#[serde(default)]
+	out_of_bounds: Vec<BoxFile>,
+	paddle: BoxFile,
+	balls: Vec<BallFile>,
+	#[serde(default)]
+	brick_types: HashMap<String, BrickTypeFile>,
+	bricks: Option<BrickGridFile>,
+	// Seconds, clearing the level faster earns a time bonus
+	par_time: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BoxFile {
+	position: Vector2<f32>,
+	size: Vector2<f32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BallFile {
+	position: Vector2<f32>,
+	velocity: Vector2<f32>,
+	radius: Option<f32>,
+	mass: Option<f32>,
+	restitution: Option<f32>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BrickTypeFile {
+	hit_points: u32,
+	// Awarded when the brick is destroyed, defaults to POINTS_PER_HIT_POINT * hit_points
+	points: Option<u32>,
+}
+
+// One character per brick, '.' or ' ' for an empty cell. Row 0 is the top one
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct BrickGridFile {
+	// Center of the top left cell
+	origin: Vector2<f32>,
+	cell: Vector2<f32>,
+	size: Vector2<f32>,
+	grid: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BoxDesc {
+	pub position: Vector2<f32>,
+	pub size: Vector2<f32>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BallDesc {
+	pub position: Vector2<f32>,
+	pub velocity: Vector2<f32>,
+	pub radius: f32,
+	pub mass: f32,
+	// Ball to ball collisions only, 0 to 1
+	pub restitution: f32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BrickDesc {
+	pub position: Vector2<f32>,
+	pub size: Vector2<f32>,
+	pub hit_points: u32,
+	pub points: u32,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Level {
+	pub name: String,
+	pub arena: Vector2<f32>,
+	pub walls: Vec<BoxDesc>,
+	// Invisible zones that take balls out of play
+	pub out_of_bounds: Vec<BoxDesc>,
+	pub paddle: BoxDesc,
+	pub balls: Vec<BallDesc>,
+	pub bricks: Vec<BrickDesc>,
+	pub par_time: f64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum LevelError {
+	// Malformed JSON or a missing/mistyped field
+	Syntax { line: usize, column: usize, message: String },
+	// Position in the brick grid, both 1 based
+	UnknownBrickType { symbol: char, row: usize, column: usize },
+	InvalidBrickType { key: String, message: String },
+	// 1 based index in the balls list
+	InvalidBall { ball: usize, message: String },
+	InvalidSize { object: String },
+	OutsideArena { object: String },
+	Overlap { first: String, second: String },
+}
+
+impl fmt::Display for LevelError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			LevelError::Syntax { line, column, ref message } => write!(f, "{}:{}: {}", line, column, message),
+			LevelError::UnknownBrickType { symbol, row, column } => write!(f, "unknown brick type '{}' in brick grid row {}, column {}", symbol, row, column),
+			LevelError::InvalidBrickType { ref key, ref message } => write!(f, "brick type '{}': {}", key, message),
+			LevelError::InvalidBall { ball, ref message } => write!(f, "ball {}: {}", ball, message),
+			LevelError::InvalidSize { ref object } => write!(f, "{} must have a positive size", object),
+			LevelError::OutsideArena { ref object } => write!(f, "{} is outside of the arena", object),
+			LevelError::Overlap { ref first, ref second } => write!(f, "{} overlaps {}", first, second),
+		}
+	}
+}
+
+impl Error for LevelError {}
+
+impl From<serde_json::Error> for LevelError {
+	fn from(error: serde_json::Error) -> Self {
+		LevelError::Syntax {
+			line: error.line(),
+			column: error.column(),
+			message: error.to_string(),
+		}
+	}
+}
+
+impl Level {
+	pub fn parse(source: &str) -> Result<Level, LevelError> {
+		let file: LevelFile = serde_json::from_str(source)?;
+
+		let mut brick_types = HashMap::new();
+		for (key, brick_type) in &file.brick_types {
+			let mut chars = key.chars();
+			let symbol = match (chars.next(), chars.next()) {
+				(Some(symbol), None) if symbol != '.' && symbol != ' ' => symbol,
+				_ => return Err(LevelError::InvalidBrickType {
+					key: key.clone(),
+					message: "key must be a single character other than '.' or ' '".to_string(),
+				}),
+			};
+			if brick_type.hit_points == 0 {
+				return Err(LevelError::InvalidBrickType {
+					key: key.clone(),
+					message: "hit_points must be at least 1".to_string(),
+				});
+			}
+			brick_types.insert(symbol, brick_type);
+		}
+
+		let mut bricks = Vec::new();
+		if let Some(ref grid) = file.bricks {
+			for (row, line) in grid.grid.iter().enumerate() {
+				for (column, symbol) in line.chars().enumerate() {
+					if symbol == '.' || symbol == ' ' {
+						continue;
+					}
+					let brick_type = brick_types.get(&symbol).ok_or(LevelError::UnknownBrickType {
+						symbol,
+						row: row + 1,
+						column: column + 1,
+					})?;
+					bricks.push(BrickDesc {
+						position: [
+							grid.origin[0] + column as f32 * grid.cell[0],
+							grid.origin[1] - row as f32 * grid.cell[1],
+						],
+						size: grid.size,
+						hit_points: brick_type.hit_points,
+						points: brick_type.points.unwrap_or(brick_type.hit_points * POINTS_PER_HIT_POINT),
+					});
+				}
+			}
+		}
+
+		let level = Level {
+			name: file.name,
+			arena: file.arena,
+			walls: file.walls.into_iter().map(|wall| BoxDesc { position: wall.position, size: wall.size }).collect(),
+			out_of_bounds: file.out_of_bounds.into_iter().map(|zone| BoxDesc { position: zone.position, size: zone.size }).collect(),
+			paddle: BoxDesc { position: file.paddle.position, size: file.paddle.size },
+			balls: file.balls.into_iter().map(|ball| BallDesc {
+				position: ball.position,
+				velocity: ball.velocity,
+				radius: ball.radius.unwrap_or(DEFAULT_BALL_RADIUS),
+				mass: ball.mass.unwrap_or(DEFAULT_BALL_MASS),
+				restitution: ball.restitution.unwrap_or(DEFAULT_BALL_RESTITUTION),
+			}).collect(),
+			bricks,
+			par_time: file.par_time.unwrap_or(DEFAULT_PAR_TIME),
+		};
+		level.validate()?;
+		Ok(level)
+	}
+
+	fn validate(&self) -> Result<(), LevelError> {
+		// Walls and out of bounds zones are allowed to overlap each other in the corners
+		for (i, ball) in self.balls.iter().enumerate() {
+			if !(ball.mass > 0.0 && ball.mass.is_finite()) {
+				return Err(LevelError::InvalidBall { ball: i + 1, message: "mass must be positive".to_string() });
+			}
+			if !(0.0..=1.0).contains(&ball.restitution) {
+				return Err(LevelError::InvalidBall { ball: i + 1, message: "restitution must be between 0 and 1".to_string() });
+			}
+		}
+
+		let mut walls = Vec::new();
+		let mut others = Vec::new();
+		for (i, wall) in self.walls.iter().enumerate() {
+			walls.push((format!("wall {}", i + 1), box_shape(wall.position, wall.size), wall.size));
+		}
+		for (i, zone) in self.out_of_bounds.iter().enumerate() {
+			walls.push((format!("out of bounds zone {}", i + 1), box_shape(zone.position, zone.size), zone.size));
+		}
+		others.push(("paddle".to_string(), box_shape(self.paddle.position, self.paddle.size), self.paddle.size));
+		for (i, ball) in self.balls.iter().enumerate() {
+			let shape: Box<dyn Shape> = Box::new(CircleObject::new(ball.position, ball.radius));
+			others.push((format!("ball {}", i + 1), shape, [ball.radius * 2.0, ball.radius * 2.0]));
+		}
+		for brick in &self.bricks {
+			let name = format!("brick at [{}, {}]", brick.position[0], brick.position[1]);
+			others.push((name, box_shape(brick.position, brick.size), brick.size));
+		}
+
+		let half_arena = [self.arena[0] / 2.0, self.arena[1] / 2.0];
+		for &(ref name, ref shape, size) in walls.iter().chain(others.iter()) {
+			if !(size[0] > 0.0 && size[1] > 0.0) {
+				return Err(LevelError::InvalidSize { object: name.clone() });
+			}
+			let position = shape.get_position();
+			if position[0] - size[0] / 2.0 < -half_arena[0] - OVERLAP_TOLERANCE
+				|| position[0] + size[0] / 2.0 > half_arena[0] + OVERLAP_TOLERANCE
+				|| position[1] - size[1] / 2.0 < -half_arena[1] - OVERLAP_TOLERANCE
+				|| position[1] + size[1] / 2.0 > half_arena[1] + OVERLAP_TOLERANCE {
+				return Err(LevelError::OutsideArena { object: name.clone() });
+			}
+		}
+
+		for (i, (name, shape, _)) in others.iter().enumerate() {
+			for (other_name, other_shape, _) in walls.iter().chain(others[(i + 1)..].iter()) {
+				if let Some((disp, _)) = get_collision(shape.as_ref(), other_shape.as_ref()) {
+					if vec2_len(disp) > OVERLAP_TOLERANCE {
+						return Err(LevelError::Overlap { first: name.clone(), second: other_name.clone() });
+					}
+				}
+			}
+		}
+		Ok(())
+	}
+}
+
+// Unit square scaled to size, matches the unit box geometry
+fn box_shape(position: Vector2<f32>, size: Vector2<f32>) -> Box<dyn Shape> {
+	let verts = vec![[-0.5, 0.5], [0.5, 0.5], [0.5, -0.5], [-0.5, -0.5]];
+	let mut shape = ConvexObject::new(position, verts);
+	shape.set_scale(size);
+	Box::new(shape)
+}
+
+fn add_box(state: &mut WebGame, desc: &BoxDesc, depth: f32, material: &Material, collision_response: Box<dyn Collidable>) -> CollidableObject {
+	let mut object = GameObject::new(state.increment_global_object_counter(), &state.geometries.unit_box_geometry, material, false, true);
+	object.position = [desc.position[0], desc.position[1], BOX_Z];
+	object.scale = [desc.size[0], desc.size[1], depth];
+	state.scene.add(&mut object);
+	CollidableObject::new(object, box_shape(desc.position, desc.size), collision_response)
+}
+
+pub fn spawn_ball(state: &mut WebGame, ball: &BallDesc) {
+	let mut sphere = GameObject::new(state.increment_global_object_counter(), &state.geometries.sphere_geometry, &state.materials.ball, true, false);
+	sphere.position = [ball.position[0], ball.position[1], BALL_Z];
+	let scale = ball.radius / BALL_GEOMETRY_RADIUS;
+	sphere.scale = [scale, scale, scale];
+	state.scene.add(&mut sphere);
+	let mut ball_object = BallObject::new(sphere, BALL_GEOMETRY_RADIUS);
+	ball_object.set_velocity(ball.velocity);
+	ball_object.set_mass(ball.mass);
+	ball_object.set_restitution(ball.restitution);
+	state.balls.push(ball_object);
+}
+
+// Replaces the current content of the game with the level
+pub fn load_level(state: &mut WebGame, level: &Level) {
+	state.scene.clear();
+	state.player = None;
+	state.balls.clear();
+	state.objects.clear();
+	state.collision_events.reset_contacts();
+	state.score.borrow_mut().on_level_start(level.par_time);
+
+	let walls_material = state.materials.walls;
+	for wall in &level.walls {
+		let wall = add_box(state, wall, WALL_DEPTH, &walls_material, Box::new(WallCollision {}));
+		state.objects.push(Box::new(wall));
+	}
+
+	// Not added to the scene, zones are never drawn
+	for zone in &level.out_of_bounds {
+		let mut object = GameObject::new(state.increment_global_object_counter(), &state.geometries.unit_box_geometry, &state.materials.default, false, false);
+		object.position = [zone.position[0], zone.position[1], BOX_Z];
+		object.scale = [zone.size[0], zone.size[1], 1.0];
+		let zone = CollidableObject::new(object, box_shape(zone.position, zone.size), Box::new(OutOfBoundsCollision {}));
+		state.objects.push(Box::new(zone));
+	}
+
+	let player_material = state.materials.player;
+	state.player = Some(add_box(state, &level.paddle, PADDLE_DEPTH, &player_material, Box::new(PlayerCollision {})));
+
+	for ball in &level.balls {
+		spawn_ball(state, ball);
+	}
+
+	for brick in &level.bricks {
+		let response = Brick::new(brick.hit_points, brick.points, &state.materials.bricks);
+		let material = *response.get_material();
+		let brick = add_box(state, &BoxDesc { position: brick.position, size: brick.size }, BRICK_DEPTH, &material, Box::new(response));
+		state.objects.push(Box::new(brick));
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use render::NullBackend;
+
+	const SMALL_LEVEL: &str = r#"{
+	"name": "Small",
+	"arena": [40.0, 40.0],
+	"walls": [
+		{ "position": [-19.0, 0.0], "size": [2.0, 40.0] },
+		{ "position": [19.0, 0.0], "size": [2.0, 40.0] }
+	],
+	"out_of_bounds": [
+		{ "position": [0.0, -19.0], "size": [40.0, 2.0] }
+	],
+	"paddle": { "position": [0.0, -15.0], "size": [10.0, 2.0] },
+	"balls": [
+		{ "position": [0.0, -10.0], "velocity": [10.0, 20.0], "radius": 1.0 }
+	],
+	"brick_types": { "a": { "hit_points": 1 }, "b": { "hit_points": 2, "points": 25 } },
+	"bricks": {
+		"origin": [-10.0, 15.0],
+		"cell": [5.0, 3.0],
+		"size": [4.0, 2.0],
+		"grid": [
+			"ab.a",
+			" b"
+		]
+	}
+}"#;
+
+	#[test]
+	fn test_parse() {
+		let level = Level::parse(SMALL_LEVEL).unwrap();
+		assert_eq!(level.name, "Small");
+		assert_eq!(level.walls.len(), 2);
+		assert_eq!(level.balls[0].radius, 1.0);
+		assert_eq!(level.balls[0].mass, DEFAULT_BALL_MASS);
+		assert_eq!(level.bricks, vec![
+			BrickDesc { position: [-10.0, 15.0], size: [4.0, 2.0], hit_points: 1, points: 10 },
+			BrickDesc { position: [-5.0, 15.0], size: [4.0, 2.0], hit_points: 2, points: 25 },
+			BrickDesc { position: [5.0, 15.0], size: [4.0, 2.0], hit_points: 1, points: 10 },
+			BrickDesc { position: [-5.0, 12.0], size: [4.0, 2.0], hit_points: 2, points: 25 },
+		]);
+	}
+
+	#[test]
+	fn test_default_level() {
+		let level = Level::parse(DEFAULT_LEVEL).unwrap();
+		assert_eq!(level.walls.len(), 3);
+		assert_eq!(level.out_of_bounds.len(), 1);
+		assert_eq!(level.balls.len(), 8);
+		assert_eq!(level.bricks.len(), 24);
+	}
+
+	#[test]
+	fn test_built_in_levels() {
+		for source in LEVELS.iter() {
+			let level = Level::parse(source).unwrap();
+			// Balls below the paddle would be lost right away
+			let paddle_top = level.paddle.position[1] + level.paddle.size[1] / 2.0;
+			for ball in &level.balls {
+				assert!(ball.position[1] - ball.radius > paddle_top, "{}: ball at {:?}", level.name, ball.position);
+			}
+		}
+	}
+
+	#[test]
+	fn test_syntax_error() {
+		let source = SMALL_LEVEL.replace("\"size\": [10.0, 2.0] },", "\"size\": [10.0, 2.0] }");
+		match Level::parse(&source) {
+			Err(LevelError::Syntax { line, .. }) => assert_eq!(line, 12),
+			other => panic!("unexpected {:?}", other),
+		}
+	}
+
+	#[test]
+	fn test_unknown_field() {
+		let source = SMALL_LEVEL.replace("\"hit_points\": 2", "\"hitpoints\": 2");
+		match Level::parse(&source) {
+			Err(LevelError::Syntax { line, .. }) => assert_eq!(line, 15),
+			other => panic!("unexpected {:?}", other),
+		}
+	}
+
+	#[test]
+	fn test_unknown_brick_type() {
+		let source = SMALL_LEVEL.replace("\" b\"", "\" bc\"");
+		assert_eq!(Level::parse(&source), Err(LevelError::UnknownBrickType { symbol: 'c', row: 2, column: 3 }));
+	}
+
+	#[test]
+	fn test_invalid_brick_type() {
+		let source = SMALL_LEVEL.replace("\"a\": { \"hit_points\": 1 }", "\"a\": { \"hit_points\": 0 }");
+		match Level::parse(&source) {
+			Err(LevelError::InvalidBrickType { key, .. }) => assert_eq!(key, "a"),
+			other => panic!("unexpected {:?}", other),
+		}
+	}
+
+	#[test]
+	fn test_invalid_ball() {
+		let source = SMALL_LEVEL.replace("\"radius\": 1.0", "\"radius\": 1.0, \"mass\": 0.0");
+		assert_eq!(Level::parse(&source), Err(LevelError::InvalidBall { ball: 1, message: "mass must be positive".to_string() }));
+
+		let source = SMALL_LEVEL.replace("\"radius\": 1.0", "\"radius\": 1.0, \"restitution\": 1.5");
+		match Level::parse(&source) {
+			Err(LevelError::InvalidBall { ball, .. }) => assert_eq!(ball, 1),
+			other => panic!("unexpected {:?}", other),
+		}
+	}
+
+	#[test]
+	fn test_overlap() {
+		let source = SMALL_LEVEL.replace("\"position\": [0.0, -10.0]", "\"position\": [0.0, -14.0]");
+		assert_eq!(Level::parse(&source), Err(LevelError::Overlap { first: "paddle".to_string(), second: "ball 1".to_string() }));
+
+		let source = SMALL_LEVEL.replace("\"origin\": [-10.0, 15.0]", "\"origin\": [-17.0, 15.0]");
+		assert_eq!(Level::parse(&source), Err(LevelError::Overlap { first: "brick at [-17, 15]".to_string(), second: "wall 1".to_string() }));
+	}
+
+	#[test]
+	fn test_outside_arena() {
+		let source = SMALL_LEVEL.replace("\"position\": [0.0, -10.0]", "\"position\": [0.0, -100.0]");
+		assert_eq!(Level::parse(&source), Err(LevelError::OutsideArena { object: "ball 1".to_string() }));
+	}
+
+	#[test]
+	fn test_ball_in_out_of_bounds_zone() {
+		let source = SMALL_LEVEL.replace("\"position\": [0.0, -10.0]", "\"position\": [0.0, -18.0]");
+		assert_eq!(Level::parse(&source), Err(LevelError::Overlap { first: "ball 1".to_string(), second: "out of bounds zone 1".to_string() }));
+	}
+
+	#[test]
+	fn test_load_level() {
+		let level = Level::parse(SMALL_LEVEL).unwrap();
+		let mut state = WebGame::new(Box::new(NullBackend));
+		load_level(&mut state, &level);
+		assert_eq!(state.balls.len(), 1);
+		assert_eq!(state.balls[0].get_velocity(), [10.0, 20.0]);
+		assert_eq!(state.objects.len(), 7);
+		assert_eq!(state.player.as_ref().unwrap().object.scale, [10.0, 2.0, PADDLE_DEPTH]);
+
+		// Loading again replaces the previous content
+		load_level(&mut state, &level);
+		assert_eq!(state.balls.len(), 1);
+		assert_eq!(state.objects.len(), 7);
+		let changes = state.scene.take_changes();
+		let last_clear = changes.iter().rposition(|change| *change == SceneChange::Clear).unwrap();
+		assert_eq!(changes.len() - last_clear - 1, 8);
+	}
+}
@@ -0,0 +1,17 @@
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate vecmath;
+
+pub mod broad_phase;
+pub mod events;
+pub mod game;
+pub mod gjk;
+pub mod level;
+pub mod polygon;
+pub mod render;
+pub mod sat;
+pub mod score;
+pub mod simulation;
+pub mod utils;
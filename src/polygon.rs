@@ -0,0 +1,268 @@
+use std::error::Error;
+use std::fmt;
+use vecmath::*;
+
+// Smaller than this and the polygon is taken to be flat
+const MIN_AREA: f32 = 0.000001;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PolygonError {
+	TooFewVerts { count: usize },
+	// Indices of two points in the same place
+	DuplicateVert { first: usize, second: usize },
+	// Indices of the points the crossing edges start at
+	SelfIntersecting { first: usize, second: usize },
+	// Too thin to enclose any area
+	ZeroArea,
+	NotConvex,
+}
+
+impl fmt::Display for PolygonError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match *self {
+			PolygonError::TooFewVerts { count } => write!(f, "a polygon needs at least 3 points, got {}", count),
+			PolygonError::DuplicateVert { first, second } => write!(f, "points {} and {} are in the same place", first, second),
+			PolygonError::SelfIntersecting { first, second } => write!(f, "the edges starting at points {} and {} cross", first, second),
+			PolygonError::ZeroArea => write!(f, "the polygon encloses no area"),
+			PolygonError::NotConvex => write!(f, "the polygon is not convex"),
+		}
+	}
+}
+
+impl Error for PolygonError {}
+
+// Positive when the points go counter clock wise
+pub fn signed_area(verts: &[Vector2<f32>]) -> f32 {
+	let mut sum = 0.0;
+	for i in 0..verts.len() {
+		let a = verts[i];
+		let b = verts[(i + 1) % verts.len()];
+		sum += a[0] * b[1] - b[0] * a[1];
+	}
+	sum / 2.0
+}
+
+// No turn goes the other way, points on a straight line are allowed
+pub fn is_convex(verts: &[Vector2<f32>]) -> bool {
+	let count = verts.len();
+	let mut sign = 0.0;
+	for i in 0..count {
+		let turn = cross(verts[i], verts[(i + 1) % count], verts[(i + 2) % count]);
+		if turn * sign < 0.0 {
+			return false;
+		}
+		if turn != 0.0 {
+			sign = turn;
+		}
+	}
+	true
+}
+
+// Checks the outline is a simple polygon, and returns it clock wise like ConvexObject wants
+pub fn validate_polygon(verts: &[Vector2<f32>]) -> Result<Vec<Vector2<f32>>, PolygonError> {
+	let count = verts.len();
+	if count < 3 {
+		return Err(PolygonError::TooFewVerts { count });
+	}
+	for first in 0..count {
+		for second in (first + 1)..count {
+			if verts[first] == verts[second] {
+				return Err(PolygonError::DuplicateVert { first, second });
+			}
+		}
+	}
+	for first in 0..count {
+		let a = verts[first];
+		let b = verts[(first + 1) % count];
+		for second in (first + 1)..count {
+			let c = verts[second];
+			let d = verts[(second + 1) % count];
+			let adjacent = second == first + 1 || (first == 0 && second == count - 1);
+			let crossing = if adjacent {
+				// Neighbours share a point, they only cross when one folds back over the other
+				let (shared, before, after) = if second == first + 1 { (b, a, d) } else { (a, b, c) };
+				cross(before, shared, after) == 0.0 && vec2_dot(vec2_sub(before, shared), vec2_sub(after, shared)) > 0.0
+			} else {
+				segments_intersect(a, b, c, d)
+			};
+			if crossing {
+				return Err(PolygonError::SelfIntersecting { first, second });
+			}
+		}
+	}
+	let area = signed_area(verts);
+	if area.abs() < MIN_AREA {
+		return Err(PolygonError::ZeroArea);
+	}
+	let mut result = verts.to_vec();
+	if area > 0.0 {
+		result.reverse();
+	}
+	Ok(result)
+}
+
+// Splits a valid polygon into convex pieces, all clock wise. Ear clipping cuts it into
+// triangles, then neighbours are merged back while the result stays convex (Hertel-Mehlhorn)
+pub fn decompose(verts: &[Vector2<f32>]) -> Vec<Vec<Vector2<f32>>> {
+	// Counter clock wise is easier to reason about
+	let mut verts = verts.to_vec();
+	if signed_area(&verts) < 0.0 {
+		verts.reverse();
+	}
+	// Points in the middle of a straight edge would only make flat triangles
+	let count = verts.len();
+	let mut outline: Vec<usize> = (0..count)
+		.filter(|&i| cross(verts[(i + count - 1) % count], verts[i], verts[(i + 1) % count]) != 0.0)
+		.collect();
+
+	let mut pieces = Vec::new();
+	while outline.len() > 3 {
+		let count = outline.len();
+		let corner = |i: usize| (outline[(i + count - 1) % count], outline[i], outline[(i + 1) % count]);
+		let is_ear = |i: usize| {
+			let (prev, cur, next) = corner(i);
+			cross(verts[prev], verts[cur], verts[next]) > 0.0 && outline.iter()
+				.filter(|&&other| other != prev && other != cur && other != next)
+				.all(|&other| !in_triangle(verts[other], verts[prev], verts[cur], verts[next]))
+		};
+		// A valid polygon always has an ear, rounding error aside
+		let ear = (0..count).find(|&i| is_ear(i)).unwrap_or(0);
+		let (prev, cur, next) = corner(ear);
+		pieces.push(vec![prev, cur, next]);
+		outline.remove(ear);
+	}
+	pieces.push(outline);
+
+	while let Some((first, second, merged)) = find_merge(&verts, &pieces) {
+		pieces[first] = merged;
+		pieces.remove(second);
+	}
+
+	pieces.iter()
+		.map(|piece| piece.iter().rev().map(|&i| verts[i]).collect())
+		.collect()
+}
+
+// Two pieces sharing an edge whose union is still convex
+fn find_merge(verts: &[Vector2<f32>], pieces: &[Vec<usize>]) -> Option<(usize, usize, Vec<usize>)> {
+	for first in 0..pieces.len() {
+		for second in (first + 1)..pieces.len() {
+			let a = &pieces[first];
+			let b = &pieces[second];
+			for i in 0..a.len() {
+				let (from, to) = (a[i], a[(i + 1) % a.len()]);
+				// Counter clock wise neighbours walk their shared edge in opposite directions
+				let j = match (0..b.len()).find(|&j| b[j] == to && b[(j + 1) % b.len()] == from) {
+					Some(j) => j,
+					None => continue,
+				};
+				let mut merged: Vec<usize> = (0..a.len()).map(|k| a[(i + 1 + k) % a.len()]).collect();
+				merged.extend((2..b.len()).map(|k| b[(j + k) % b.len()]));
+				let points: Vec<Vector2<f32>> = merged.iter().map(|&k| verts[k]).collect();
+				if is_convex(&points) {
+					return Some((first, second, merged));
+				}
+			}
+		}
+	}
+	None
+}
+
+// Positive when a, b, c turn left
+fn cross(a: Vector2<f32>, b: Vector2<f32>, c: Vector2<f32>) -> f32 {
+	let ab = vec2_sub(b, a);
+	let bc = vec2_sub(c, b);
+	ab[0] * bc[1] - ab[1] * bc[0]
+}
+
+// Counter clock wise triangle, points on the edges count as inside
+fn in_triangle(point: Vector2<f32>, a: Vector2<f32>, b: Vector2<f32>, c: Vector2<f32>) -> bool {
+	cross(a, b, point) >= 0.0 && cross(b, c, point) >= 0.0 && cross(c, a, point) >= 0.0
+}
+
+// Touching counts
+fn segments_intersect(a: Vector2<f32>, b: Vector2<f32>, c: Vector2<f32>, d: Vector2<f32>) -> bool {
+	let side = |value: f32| if value > 0.0 { 1 } else if value < 0.0 { -1 } else { 0 };
+	let d1 = side(cross(a, b, c));
+	let d2 = side(cross(a, b, d));
+	let d3 = side(cross(c, d, a));
+	let d4 = side(cross(c, d, b));
+	if d1 * d2 < 0 && d3 * d4 < 0 {
+		return true;
+	}
+	let within = |p: Vector2<f32>, from: Vector2<f32>, to: Vector2<f32>| {
+		p[0] >= from[0].min(to[0]) && p[0] <= from[0].max(to[0]) && p[1] >= from[1].min(to[1]) && p[1] <= from[1].max(to[1])
+	};
+	(d1 == 0 && within(c, a, b)) || (d2 == 0 && within(d, a, b)) ||
+		(d3 == 0 && within(a, c, d)) || (d4 == 0 && within(b, c, d))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn l_shape() -> Vec<Vector2<f32>> {
+		vec![[0.0, 0.0], [4.0, 0.0], [4.0, 1.0], [1.0, 1.0], [1.0, 4.0], [0.0, 4.0]]
+	}
+
+	fn total_area(pieces: &[Vec<Vector2<f32>>]) -> f32 {
+		pieces.iter().map(|piece| signed_area(piece)).sum()
+	}
+
+	#[test]
+	fn test_validate() {
+		// Counter clock wise comes back clock wise
+		let verts = validate_polygon(&l_shape()).unwrap();
+		assert!(signed_area(&verts) < 0.0);
+		assert_eq!(validate_polygon(&verts).unwrap(), verts);
+
+		assert_eq!(validate_polygon(&[[0.0, 0.0], [1.0, 0.0]]), Err(PolygonError::TooFewVerts { count: 2 }));
+		assert_eq!(validate_polygon(&[[0.0, 0.0], [1.0, 0.0], [0.5, 0.000001]]), Err(PolygonError::ZeroArea));
+		assert_eq!(
+			validate_polygon(&[[0.0, 0.0], [1.0, 0.0], [1.0, 1.0], [1.0, 0.0]]),
+			Err(PolygonError::DuplicateVert { first: 1, second: 3 }));
+		// Bow tie
+		assert_eq!(
+			validate_polygon(&[[0.0, 0.0], [2.0, 2.0], [2.0, 0.0], [0.0, 2.0]]),
+			Err(PolygonError::SelfIntersecting { first: 0, second: 2 }));
+		// Spike folding back along its own edge
+		assert_eq!(
+			validate_polygon(&[[0.0, 0.0], [4.0, 0.0], [2.0, 0.0], [2.0, 2.0]]),
+			Err(PolygonError::SelfIntersecting { first: 0, second: 1 }));
+	}
+
+	#[test]
+	fn test_decompose_convex() {
+		let verts = validate_polygon(&[[-1.0, 1.0], [1.0, 1.0], [1.0, -1.0], [-1.0, -1.0]]).unwrap();
+		let pieces = decompose(&verts);
+		assert_eq!(pieces.len(), 1);
+		assert_eq!(pieces[0].len(), 4);
+	}
+
+	#[test]
+	fn test_decompose_concave() {
+		let verts = validate_polygon(&l_shape()).unwrap();
+		let pieces = decompose(&verts);
+		assert_eq!(pieces.len(), 2);
+		for piece in &pieces {
+			assert!(is_convex(piece));
+			assert!(signed_area(piece) < 0.0);
+		}
+		assert!((total_area(&pieces) - signed_area(&verts)).abs() < 0.0001);
+
+		// Five pointed star
+		let mut star = Vec::new();
+		for i in 0..10 {
+			let angle = i as f32 * ::std::f32::consts::PI / 5.0;
+			let radius = if i % 2 == 0 { 2.0 } else { 0.8 };
+			star.push([angle.cos() * radius, angle.sin() * radius]);
+		}
+		let verts = validate_polygon(&star).unwrap();
+		let pieces = decompose(&verts);
+		assert!(pieces.len() >= 5);
+		for piece in &pieces {
+			assert!(is_convex(piece));
+		}
+		assert!((total_area(&pieces) - signed_area(&verts)).abs() < 0.0001);
+	}
+}
@@ -0,0 +1,204 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+use vecmath::*;
+
+// Front end the simulation is presented with. Objects are referred to by GameObject ids
+pub trait RenderBackend {
+	fn create_mesh(&mut self, id: u32, geometry: u32, material: u32, cast_shadows: bool, receive_shadows: bool);
+	// Rotation is around the z axis, counter clock wise in radians
+	fn set_transform(&mut self, id: u32, position: Vector3<f32>, rotation: f32, scale: Vector3<f32>);
+	fn set_material(&mut self, id: u32, material: u32);
+	fn add_to_scene(&mut self, id: u32);
+	fn remove_from_scene(&mut self, id: u32);
+	fn clear_scene(&mut self);
+	fn render(&mut self);
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DrawCommand {
+	CreateMesh {
+		id: u32,
+		geometry: u32,
+		material: u32,
+		cast_shadows: bool,
+		receive_shadows: bool,
+	},
+	SetTransform {
+		id: u32,
+		position: Vector3<f32>,
+		rotation: f32,
+		scale: Vector3<f32>,
+	},
+	SetMaterial {
+		id: u32,
+		material: u32,
+	},
+	AddToScene(u32),
+	RemoveFromScene(u32),
+	ClearScene,
+	Render,
+}
+
+pub struct NullBackend;
+
+impl RenderBackend for NullBackend {
+	fn create_mesh(&mut self, _id: u32, _geometry: u32, _material: u32, _cast_shadows: bool, _receive_shadows: bool) {}
+	fn set_transform(&mut self, _id: u32, _position: Vector3<f32>, _rotation: f32, _scale: Vector3<f32>) {}
+	fn set_material(&mut self, _id: u32, _material: u32) {}
+	fn add_to_scene(&mut self, _id: u32) {}
+	fn remove_from_scene(&mut self, _id: u32) {}
+	fn clear_scene(&mut self) {}
+	fn render(&mut self) {}
+}
+
+// Keeps every command it receives. Clones share the same command list,
+// so a handle can be kept around after the backend is moved into WebGame
+#[derive(Debug, Clone, Default)]
+pub struct RecordingBackend {
+	commands: Rc<RefCell<Vec<DrawCommand>>>,
+}
+
+impl RecordingBackend {
+	pub fn new() -> Self {
+		RecordingBackend::default()
+	}
+
+	pub fn commands(&self) -> Vec<DrawCommand> {
+		self.commands.borrow().clone()
+	}
+
+	pub fn take_commands(&self) -> Vec<DrawCommand> {
+		self.commands.borrow_mut().drain(..).collect()
+	}
+
+	fn record(&mut self, command: DrawCommand) {
+		self.commands.borrow_mut().push(command);
+	}
+}
+
+impl RenderBackend for RecordingBackend {
+	fn create_mesh(&mut self, id: u32, geometry: u32, material: u32, cast_shadows: bool, receive_shadows: bool) {
+		self.record(DrawCommand::CreateMesh {
+			id,
+			geometry,
+			material,
+			cast_shadows,
+			receive_shadows,
+		});
+	}
+
+	fn set_transform(&mut self, id: u32, position: Vector3<f32>, rotation: f32, scale: Vector3<f32>) {
+		self.record(DrawCommand::SetTransform { id, position, rotation, scale });
+	}
+
+	fn set_material(&mut self, id: u32, material: u32) {
+		self.record(DrawCommand::SetMaterial { id, material });
+	}
+
+	fn add_to_scene(&mut self, id: u32) {
+		self.record(DrawCommand::AddToScene(id));
+	}
+
+	fn remove_from_scene(&mut self, id: u32) {
+		self.record(DrawCommand::RemoveFromScene(id));
+	}
+
+	fn clear_scene(&mut self) {
+		self.record(DrawCommand::ClearScene);
+	}
+
+	fn render(&mut self) {
+		self.record(DrawCommand::Render);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use game::{GameState, WebGame};
+	use simulation::{initial_render_call, update};
+
+	fn count(commands: &[DrawCommand], filter: fn(&DrawCommand) -> bool) -> usize {
+		commands.iter().filter(|c| filter(c)).count()
+	}
+
+	#[test]
+	fn test_initial_frame() {
+		let backend = RecordingBackend::new();
+		let mut state = WebGame::new(Box::new(backend.clone()));
+		initial_render_call(&mut state);
+		state.render(1.0);
+
+		let commands = backend.take_commands();
+		assert_eq!(count(&commands, |c| matches!(*c, DrawCommand::CreateMesh { .. })), 36);
+		assert_eq!(count(&commands, |c| matches!(*c, DrawCommand::AddToScene(_))), 36);
+		assert_eq!(count(&commands, |c| matches!(*c, DrawCommand::SetTransform { .. })), 36);
+		assert_eq!(commands.last(), Some(&DrawCommand::Render));
+
+		let player = state.player.as_ref().unwrap().object.get_id();
+		let create = commands.iter().position(|c| *c == DrawCommand::AddToScene(player)).unwrap();
+		let transform = commands.iter().position(|c| *c == DrawCommand::SetTransform { id: player, position: [0.0, -40.0, 2.5], rotation: 0.0, scale: [20.0, 3.0, 8.0] }).unwrap();
+		assert!(create < transform);
+	}
+
+	#[test]
+	fn test_transforms_follow_simulation() {
+		let backend = RecordingBackend::new();
+		let mut state = WebGame::new(Box::new(backend.clone()));
+		initial_render_call(&mut state);
+		state.game_state = GameState::Playing;
+		state.render(1.0);
+		backend.take_commands();
+
+		state.mouse_x = 20.0;
+		update(&mut state, 1.0 / 60.0);
+		state.render(1.0);
+
+		let commands = backend.take_commands();
+		assert_eq!(count(&commands, |c| matches!(*c, DrawCommand::CreateMesh { .. })), 0);
+		let player = state.player.as_ref().unwrap().object.get_id();
+		assert!(commands.contains(&DrawCommand::SetTransform { id: player, position: [20.0, -40.0, 2.5], rotation: 0.0, scale: [20.0, 3.0, 8.0] }));
+		for ball in &state.balls {
+			let position = ball.get_position();
+			assert!(commands.iter().any(|c| matches!(*c,
+				DrawCommand::SetTransform { position: p, .. } if p[0] == position[0] && p[1] == position[1])));
+		}
+		assert_eq!(commands.last(), Some(&DrawCommand::Render));
+	}
+
+	#[test]
+	fn test_rotation_and_scale_are_synced() {
+		let backend = RecordingBackend::new();
+		let mut state = WebGame::new(Box::new(backend.clone()));
+		initial_render_call(&mut state);
+		state.game_state = GameState::Playing;
+		state.render(1.0);
+		backend.take_commands();
+
+		state.player.as_mut().unwrap().object.rotation = 0.25;
+		state.player.as_mut().unwrap().object.scale = [30.0, 3.0, 8.0];
+		update(&mut state, 1.0 / 60.0);
+		state.render(1.0);
+
+		let player = state.player.as_ref().unwrap().object.get_id();
+		assert!(backend.take_commands().contains(&DrawCommand::SetTransform {
+			id: player,
+			position: [0.0, -40.0, 2.5],
+			rotation: 0.25,
+			scale: [30.0, 3.0, 8.0],
+		}));
+	}
+
+	#[test]
+	fn test_clean_objects_are_not_synced() {
+		let backend = RecordingBackend::new();
+		let mut state = WebGame::new(Box::new(backend.clone()));
+		initial_render_call(&mut state);
+		state.game_state = GameState::Playing;
+		state.render(1.0);
+		backend.take_commands();
+
+		state.render(1.0);
+		assert_eq!(backend.take_commands(), vec![DrawCommand::Render]);
+	}
+}
@@ -1,60 +1,155 @@
+use gjk::{get_collision_gjk, get_distance_gjk, get_moved_distance_gjk};
+use polygon::{decompose, is_convex, validate_polygon, PolygonError};
 use utils::{min, max};
 use vecmath::*;
 
 use std::f32;
 
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ShapeType {
 	Convex,
 	Circle,
+	Capsule,
+	Segment,
+	Aabb,
+	Chain,
+	Compound,
 }
 
 // min, max
 pub type Interval = Vector2<f32>;
 
+// Slack for floating point error when deciding whether a point is inside a shape
+const CONTACT_TOLERANCE: f32 = 0.0001;
+
+// Axis aligned bounding box, in world space
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Aabb {
+	pub min: Vector2<f32>,
+	pub max: Vector2<f32>,
+}
+
+impl Aabb {
+	pub fn new(min: Vector2<f32>, max: Vector2<f32>) -> Self {
+		Aabb { min, max }
+	}
+
+	pub fn from_points(points: &[Vector2<f32>]) -> Self {
+		let mut result = Aabb::new([f32::MAX, f32::MAX], [f32::MIN, f32::MIN]);
+		for point in points {
+			result.min = [min(result.min[0], point[0]), min(result.min[1], point[1])];
+			result.max = [max(result.max[0], point[0]), max(result.max[1], point[1])];
+		}
+		result
+	}
+
+	// Grown by margin on every side
+	pub fn expanded(&self, margin: f32) -> Aabb {
+		Aabb::new([self.min[0] - margin, self.min[1] - margin], [self.max[0] + margin, self.max[1] + margin])
+	}
+
+	// Touching boxes count as overlapping
+	pub fn overlaps(&self, other: &Aabb) -> bool {
+		self.min[0] <= other.max[0] && self.max[0] >= other.min[0] &&
+			self.min[1] <= other.max[1] && self.max[1] >= other.min[1]
+	}
+}
+
 pub trait Shape {
 	fn get_type(&self) -> ShapeType;
 	fn set_position(&mut self, position: Vector2<f32>);
 	fn get_position(&self) -> Vector2<f32>;
-	fn get_verts(&self) -> Vec<Vector2<f32>>;
-	fn get_normals_to_test_against(&self, other_shape: &Shape) -> Vec<Vector2<f32>>;
+	// Counter clock wise, in radians
+	fn set_rotation(&mut self, rotation: f32);
+	fn get_rotation(&self) -> f32;
+	// Applied in local space, before rotation
+	fn set_scale(&mut self, scale: Vector2<f32>);
+	fn get_scale(&self) -> Vector2<f32>;
+	// World space. A circle is its center, a capsule or a segment its two ends and a chain
+	// its points, closed chains end with the first point again. Passed as a slice so the
+	// shapes can hand out cached or stack allocated points
+	fn visit_verts(&self, visit: &mut dyn FnMut(&[Vector2<f32>]));
+	// Rounding around the verts, circles and capsules are a rounded point and segment
+	fn get_radius(&self) -> f32 {
+		0.0
+	}
+	// Furthest of the verts along direction, without the radius
+	fn get_support(&self, direction: Vector2<f32>) -> Vector2<f32> {
+		let mut result = [0.0, 0.0];
+		self.visit_verts(&mut |verts| result = support_point(verts, direction));
+		result
+	}
+	// Segments of a chain, world space. Other shapes have none
+	fn get_segments(&self) -> &[SegmentObject] {
+		&[]
+	}
+	// Convex parts of a compound shape, world space. Other shapes have none
+	fn get_pieces(&self) -> &[ConvexObject] {
+		&[]
+	}
+	// Axes for the separating axis test, one call each, not necessarily unique
+	fn visit_normals_to_test_against(&self, other_shape: &dyn Shape, visit: &mut dyn FnMut(Vector2<f32>));
 	fn calculate_projection_on(&self, vector: &Vector2<f32>) -> Interval;
+	fn get_aabb(&self) -> Aabb;
 }
 
-#[derive(Debug)]
+// The transform is only changed through the setters, which keep the world space verts and
+// normals cached so collision tests do not have to allocate
+#[derive(Debug, Clone)]
 pub struct ConvexObject {
-	pub position: Vector2<f32>,
+	position: Vector2<f32>,
 
 	// Note: clock wise winding, origin at 0.0
-	pub verts: Vec<Vector2<f32>>,
-	pub scale: Vector2<f32>, 
+	verts: Vec<Vector2<f32>>,
+	scale: Vector2<f32>,
+	rotation: f32,
+
+	world_verts: Vec<Vector2<f32>>,
+	// Do not depend on the position
+	normals: Vec<Vector2<f32>>,
 }
 
 impl ConvexObject {
+	// Trusts the verts, see try_new for anything not known to be valid
 	pub fn new(position: Vector2<f32>, verts: Vec<Vector2<f32>>) -> Self {
-		ConvexObject {
-			position: position,
-			verts: verts,
-			scale: [1.0, 1.0], //For now
+		assert!(verts.len() >= 3);
+		let mut result = ConvexObject {
+			position,
+			world_verts: verts.clone(),
+			normals: Vec::with_capacity(verts.len()),
+			verts,
+			scale: [1.0, 1.0],
+			rotation: 0.0,
+		};
+		result.update_world_verts();
+		result.update_normals();
+		result
+	}
+
+	// Checks the verts form a convex polygon, counter clock wise ones are reversed
+	pub fn try_new(position: Vector2<f32>, verts: Vec<Vector2<f32>>) -> Result<Self, PolygonError> {
+		let verts = validate_polygon(&verts)?;
+		if !is_convex(&verts) {
+			return Err(PolygonError::NotConvex);
 		}
+		Ok(ConvexObject::new(position, verts))
 	}
 
-	fn calculate_normals(&self) -> Vec<Vector2<f32>> {
-		assert!(self.verts.len() >= 3);
-		let mut result = Vec::new();
-		let mut prev = &self.verts[0];
-		let vert_counts = self.verts.len(); 
-		for i in 1..(vert_counts + 1) {
-			let index = i % vert_counts;
-			let cur = &self.verts[index];
-			result.push(calculate_normal(*prev, *cur));
-			prev = cur;
+	// Relative to the position, before scale and rotation
+	pub fn get_local_verts(&self) -> &[Vector2<f32>] {
+		&self.verts
+	}
+
+	fn update_world_verts(&mut self) {
+		for (world, vert) in self.world_verts.iter_mut().zip(&self.verts) {
+			*world = to_world(*vert, self.position, self.scale, self.rotation);
 		}
-		result
 	}
 
-	// Note: only position offset and scale supported for now 
-	fn transfrom_vec(&self, vector: &Vector2<f32>) -> Vector2<f32> {
-		vec2_mul(vec2_add(*vector, self.get_position()), self.scale)
+	fn update_normals(&mut self) {
+		let verts = &self.world_verts;
+		self.normals.clear();
+		self.normals.extend((0..verts.len()).map(|i| calculate_normal(verts[i], verts[(i + 1) % verts.len()])));
 	}
 }
 
@@ -63,36 +158,63 @@ impl Shape for ConvexObject {
 		ShapeType::Convex
 	}
 
+	// Objects are moved every frame whether they changed or not, so the caches are only
+	// rebuilt on a real change
 	fn set_position(&mut self, position: Vector2<f32>) {
-		self.position = position;
+		if position != self.position {
+			self.position = position;
+			self.update_world_verts();
+		}
 	}
 
 	fn get_position(&self) -> Vector2<f32> {
 		self.position
 	}
 
-	fn get_verts(&self) -> Vec<Vector2<f32>> {
-		self.verts.clone()
+	fn set_rotation(&mut self, rotation: f32) {
+		if rotation != self.rotation {
+			self.rotation = rotation;
+			self.update_world_verts();
+			self.update_normals();
+		}
+	}
+
+	fn get_rotation(&self) -> f32 {
+		self.rotation
 	}
-	
-	fn get_normals_to_test_against(&self, _other_shape: &Shape) -> Vec<Vector2<f32>> {
-		self.calculate_normals()
+
+	fn set_scale(&mut self, scale: Vector2<f32>) {
+		if scale != self.scale {
+			self.scale = scale;
+			self.update_world_verts();
+			self.update_normals();
+		}
 	}
-	
-	fn calculate_projection_on(&self, normal: &Vector2<f32>) -> Interval {
-		let mut cur_min = f32::MAX;
-		let mut cur_max = f32::MIN;
-		for vert in &self.verts {
-			let cur = self.transfrom_vec(vert);
-			let dot = vec2_dot(*normal, cur);
-			if dot < cur_min {
-				cur_min = dot;
-			}
-			if dot > cur_max {
-				cur_max = dot;
-			}
+
+	fn get_scale(&self) -> Vector2<f32> {
+		self.scale
+	}
+
+	fn visit_verts(&self, visit: &mut dyn FnMut(&[Vector2<f32>])) {
+		visit(&self.world_verts);
+	}
+
+	fn visit_normals_to_test_against(&self, _other_shape: &dyn Shape, visit: &mut dyn FnMut(Vector2<f32>)) {
+		for normal in &self.normals {
+			visit(*normal);
 		}
-		[cur_min, cur_max]
+	}
+
+	fn get_support(&self, direction: Vector2<f32>) -> Vector2<f32> {
+		support_point(&self.world_verts, direction)
+	}
+
+	fn calculate_projection_on(&self, normal: &Vector2<f32>) -> Interval {
+		project_points(&self.world_verts, normal)
+	}
+
+	fn get_aabb(&self) -> Aabb {
+		Aabb::from_points(&self.world_verts)
 	}
 }
 
@@ -100,17 +222,22 @@ impl Shape for ConvexObject {
 pub struct CircleObject {
 	pub position: Vector2<f32>,
 	pub radius: f32,
-	pub scale: Vector2<f32>, 
+	// Only uniform scale is supported
+	pub scale: f32,
 }
 
 impl CircleObject {
 	pub fn new(position: Vector2<f32>, radius: f32) -> Self {
 		CircleObject {
-			position: position,
-			radius: radius,
-			scale: [1.0, 1.0], //For now
+			position,
+			radius,
+			scale: 1.0,
 		}
 	}
+
+	pub fn get_scaled_radius(&self) -> f32 {
+		self.radius * self.scale
+	}
 }
 
 impl Shape for CircleObject {
@@ -126,163 +253,2051 @@ impl Shape for CircleObject {
 		self.position
 	}
 
-	fn get_verts(&self) -> Vec<Vector2<f32>> {
-		unimplemented!()
+	// Rotation does not change a circle
+	fn set_rotation(&mut self, _rotation: f32) {
+	}
+
+	fn get_rotation(&self) -> f32 {
+		0.0
+	}
+
+	// Non uniform scale would turn the circle into an ellipse, the larger component is used
+	fn set_scale(&mut self, scale: Vector2<f32>) {
+		self.scale = max(scale[0].abs(), scale[1].abs());
+	}
+
+	fn get_scale(&self) -> Vector2<f32> {
+		[self.scale, self.scale]
+	}
+
+	fn visit_verts(&self, visit: &mut dyn FnMut(&[Vector2<f32>])) {
+		visit(&[self.position]);
+	}
+
+	fn get_radius(&self) -> f32 {
+		self.get_scaled_radius()
+	}
+
+	fn get_support(&self, _direction: Vector2<f32>) -> Vector2<f32> {
+		self.position
 	}
 	
-	fn get_normals_to_test_against(&self, other_shape: &Shape) -> Vec<Vector2<f32>> {
-		let mut result = Vec::new();
+	fn visit_normals_to_test_against(&self, other_shape: &dyn Shape, visit: &mut dyn FnMut(Vector2<f32>)) {
 		match other_shape.get_type() {
 			ShapeType::Circle => {
-				result.push(vec2_normalized(vec2_sub(other_shape.get_position(), self.position)));
-			}
-			ShapeType::Convex => {
-				for vert in other_shape.get_verts() {
-					result.push(vec2_normalized(vec2_sub(vert, self.position)));
+				let axis = vec2_sub(other_shape.get_position(), self.position);
+				if vec2_square_len(axis) > 0.0 {
+					visit(vec2_normalized(axis));
 				}
 			}
+			_ => rounded_axes(&[self.position], other_shape, visit),
 		}
-		result
 	}
 	
 	fn calculate_projection_on(&self, normal: &Vector2<f32>) -> Interval {
 		let dot = vec2_dot(*normal, self.get_position());
-		[dot - self.radius, dot + self.radius]
+		let radius = self.get_scaled_radius();
+		[dot - radius, dot + radius]
+	}
+
+	fn get_aabb(&self) -> Aabb {
+		let radius = self.get_scaled_radius();
+		Aabb::new(vec2_sub(self.position, [radius, radius]), vec2_add(self.position, [radius, radius]))
 	}
 }
 
-// minimum displacement and normal
-pub type CollistionInfo = Option<(Vector2<f32>, Vector2<f32>)>;
+// Segment with rounded ends, lying along the local x axis
+#[derive(Debug)]
+pub struct CapsuleObject {
+	pub position: Vector2<f32>,
+	// From the center to either end of the segment
+	pub half_length: f32,
+	pub radius: f32,
+	// x stretches the segment and y the radius
+	pub scale: Vector2<f32>,
+	pub rotation: f32,
+}
 
-pub fn get_collision(a: &Shape, b: &Shape) -> CollistionInfo {
-	let mut min_overlap_len = f32::MAX;
-	let mut min_overlap_vec = [0.0, 0.0];
-	for normals_to_test in [a.get_normals_to_test_against(b), b.get_normals_to_test_against(a)].iter() {
-		for normal in normals_to_test {
-			let proj_a = a.calculate_projection_on(&normal);
-			let proj_b = b.calculate_projection_on(&normal);
-			let overlap = calculate_overlap(&proj_a, &proj_b);
-			match overlap {
-				Some(overlap) => {
-					let lenght = overlap[1] - overlap[0];
-					if lenght < min_overlap_len {
-						min_overlap_len = lenght;
-						min_overlap_vec = vec2_sub(
-							vec2_mul(*normal, [overlap[1], overlap[1]]), 
-							vec2_mul(*normal, [overlap[0], overlap[0]]));
-					}
-				},
-				None => {
-					return None
-				}
-			}
+impl CapsuleObject {
+	pub fn new(position: Vector2<f32>, half_length: f32, radius: f32) -> Self {
+		CapsuleObject {
+			position,
+			half_length,
+			radius,
+			scale: [1.0, 1.0],
+			rotation: 0.0,
 		}
 	}
-	let direction = vec2_sub(b.get_position(), a.get_position());
-	let direction = vec2_dot(direction, min_overlap_vec);
-	if direction < 0.0 {
-		Some((min_overlap_vec, vec2_normalized(min_overlap_vec)))
-	} else {
-		Some((vec2_neg(min_overlap_vec), vec2_normalized(vec2_neg(min_overlap_vec))))
+
+	fn get_ends(&self) -> [Vector2<f32>; 2] {
+		let end = |x: f32| to_world([x, 0.0], self.position, [self.scale[0], 1.0], self.rotation);
+		[end(-self.half_length), end(self.half_length)]
 	}
 }
 
-fn calculate_normal(a: Vector2<f32>, b: Vector2<f32>) -> Vector2<f32> {
-	let diff = vec2_sub(b, a);
-	vec2_normalized([-diff[1], diff[0]])
+impl Shape for CapsuleObject {
+	fn get_type(&self) -> ShapeType {
+		ShapeType::Capsule
+	}
+
+	fn set_position(&mut self, position: Vector2<f32>) {
+		self.position = position;
+	}
+
+	fn get_position(&self) -> Vector2<f32> {
+		self.position
+	}
+
+	fn set_rotation(&mut self, rotation: f32) {
+		self.rotation = rotation;
+	}
+
+	fn get_rotation(&self) -> f32 {
+		self.rotation
+	}
+
+	fn set_scale(&mut self, scale: Vector2<f32>) {
+		self.scale = scale;
+	}
+
+	fn get_scale(&self) -> Vector2<f32> {
+		self.scale
+	}
+
+	fn visit_verts(&self, visit: &mut dyn FnMut(&[Vector2<f32>])) {
+		visit(&self.get_ends());
+	}
+
+	fn get_radius(&self) -> f32 {
+		self.radius * self.scale[1].abs()
+	}
+
+	fn visit_normals_to_test_against(&self, other_shape: &dyn Shape, visit: &mut dyn FnMut(Vector2<f32>)) {
+		let ends = self.get_ends();
+		segment_axes(ends[0], ends[1], visit);
+		rounded_axes(&ends, other_shape, visit);
+	}
+
+	fn calculate_projection_on(&self, normal: &Vector2<f32>) -> Interval {
+		let projection = project_points(&self.get_ends(), normal);
+		let radius = self.get_radius();
+		[projection[0] - radius, projection[1] + radius]
+	}
+
+	fn get_aabb(&self) -> Aabb {
+		Aabb::from_points(&self.get_ends()).expanded(self.get_radius())
+	}
 }
 
-fn calculate_overlap(a: &Interval, b: &Interval) -> Option<Interval> {
-	if a[0] < b[1] && a[1] > b[0] {
-		return Some([max(a[0], b[0]), min(a[1], b[1])])
+#[derive(Debug)]
+pub struct SegmentObject {
+	pub position: Vector2<f32>,
+	// Ends, relative to the position
+	pub verts: [Vector2<f32>; 2],
+	pub scale: Vector2<f32>,
+	pub rotation: f32,
+}
+
+impl SegmentObject {
+	// Ends in world space, the position is placed between them
+	pub fn new(a: Vector2<f32>, b: Vector2<f32>) -> Self {
+		let position = vec2_scale(vec2_add(a, b), 0.5);
+		SegmentObject {
+			position,
+			verts: [vec2_sub(a, position), vec2_sub(b, position)],
+			scale: [1.0, 1.0],
+			rotation: 0.0,
+		}
+	}
+
+	fn get_ends(&self) -> [Vector2<f32>; 2] {
+		let end = |vert: Vector2<f32>| to_world(vert, self.position, self.scale, self.rotation);
+		[end(self.verts[0]), end(self.verts[1])]
 	}
-	None
 }
 
-#[cfg(test)]
-mod tests {
-	use super::*;
+impl Shape for SegmentObject {
+	fn get_type(&self) -> ShapeType {
+		ShapeType::Segment
+	}
 
-	#[test]
-	fn test_overlap() {
-		eq([1.0, 2.0], calculate_overlap(&[0.0, 2.0], &[1.0, 5.0]).unwrap());
-		eq([1.0, 2.0], calculate_overlap(&[1.0, 5.0], &[0.0, 2.0]).unwrap());
-		assert_eq!(None, calculate_overlap(&[1.0, 2.0], &[3.0, 4.0]));
-		assert_eq!(None, calculate_overlap(&[3.0, 4.0], &[1.0, 2.0]));
+	fn set_position(&mut self, position: Vector2<f32>) {
+		self.position = position;
 	}
 
-	#[test]
-	fn test_collision_1() {
-		let verts = vec![[-1.0, 1.0], [1.0, 1.0], [1.0, -1.0], [-1.0, -1.0]];
-		let obj_a = ConvexObject::new([0.0, 0.0], verts.clone());
-		let obj_b = ConvexObject::new([1.0, 0.0], verts.clone());
-		let collision = get_collision(&obj_a, &obj_b);
-		eq(collision.unwrap().0, [-1.0, 0.0]);
+	fn get_position(&self) -> Vector2<f32> {
+		self.position
+	}
 
-		let collision = get_collision(&obj_b, &obj_a);
-		eq(collision.unwrap().0, [1.0, 0.0]);
+	fn set_rotation(&mut self, rotation: f32) {
+		self.rotation = rotation;
 	}
 
-	#[test]
-	fn test_collision_2() {
-		let verts = vec![[-1.0, 1.0], [1.0, 1.0], [1.0, -1.0], [-1.0, -1.0]];
-		let obj_a = ConvexObject::new([0.0, 0.0], verts.clone());
-		let obj_b = ConvexObject::new([3.0, 0.0], verts.clone());
-		let collision = get_collision(&obj_a, &obj_b);
-		assert_eq!(collision, None);
+	fn get_rotation(&self) -> f32 {
+		self.rotation
+	}
 
-		let collision = get_collision(&obj_b, &obj_a);
-		assert_eq!(collision, None);
+	fn set_scale(&mut self, scale: Vector2<f32>) {
+		self.scale = scale;
 	}
 
-	#[test]
-	fn test_collision_3() {
-		let obj_a = CircleObject::new([0.0, 0.0], 3.0);
-		let obj_b = CircleObject::new([3.0, 3.0], 2.0);
-		let collision = get_collision(&obj_a, &obj_b);
-		eq(collision.unwrap().0, [-0.5355338, -0.5355338]);
+	fn get_scale(&self) -> Vector2<f32> {
+		self.scale
+	}
 
-		let collision = get_collision(&obj_b, &obj_a);
-		eq(collision.unwrap().0, [0.5355338, 0.5355338]);
+	fn visit_verts(&self, visit: &mut dyn FnMut(&[Vector2<f32>])) {
+		visit(&self.get_ends());
 	}
 
-	#[test]
-	fn test_collision_4() {
-		let obj_a = CircleObject::new([0.0, 0.0], 3.0);
-		let obj_b = CircleObject::new([4.0, 4.0], 2.0);
-		let collision = get_collision(&obj_a, &obj_b);
-		assert_eq!(collision, None);
+	fn visit_normals_to_test_against(&self, other_shape: &dyn Shape, visit: &mut dyn FnMut(Vector2<f32>)) {
+		let ends = self.get_ends();
+		if ends[0] == ends[1] {
+			// A point, tested like the center of a circle
+			rounded_axes(&ends[..1], other_shape, visit);
+		} else {
+			segment_axes(ends[0], ends[1], visit);
+		}
+	}
 
-		let collision = get_collision(&obj_b, &obj_a);
-		assert_eq!(collision, None);
+	fn calculate_projection_on(&self, normal: &Vector2<f32>) -> Interval {
+		project_points(&self.get_ends(), normal)
 	}
 
-	#[test]
-	fn test_collision_5() {
-		let verts = vec![[-1.0, 1.0], [1.0, 1.0], [1.0, -1.0], [-1.0, -1.0]];
-		let obj_a = ConvexObject::new([0.0, 0.0], verts.clone());
-		let obj_b = CircleObject::new([2.0, 0.0], 1.5);
-		let collision = get_collision(&obj_a, &obj_b);
-		eq(collision.unwrap().0, [-0.5, 0.0]);
+	fn get_aabb(&self) -> Aabb {
+		Aabb::from_points(&self.get_ends())
+	}
+}
 
-		let collision = get_collision(&obj_b, &obj_a);
-		eq(collision.unwrap().0, [0.5, 0.0]);
+// Box that never rotates, cheaper to test than the same ConvexObject
+#[derive(Debug)]
+pub struct AabbObject {
+	pub position: Vector2<f32>,
+	pub half_extents: Vector2<f32>,
+	pub scale: Vector2<f32>,
+}
+
+impl AabbObject {
+	pub fn new(position: Vector2<f32>, half_extents: Vector2<f32>) -> Self {
+		AabbObject {
+			position,
+			half_extents,
+			scale: [1.0, 1.0],
+		}
 	}
 
-	#[test]
-	fn test_collision_6() {
-		let verts = vec![[-1.0, 1.0], [1.0, 1.0], [1.0, -1.0], [-1.0, -1.0]];
-		let obj_a = ConvexObject::new([0.0, 0.0], verts.clone());
-		let obj_b = CircleObject::new([2.0, 2.0], 2.0);
-		let collision = get_collision(&obj_a, &obj_b);
-		eq(collision.unwrap().0, [-0.41421348, -0.41421348]);
+	pub fn get_scaled_half_extents(&self) -> Vector2<f32> {
+		[(self.half_extents[0] * self.scale[0]).abs(), (self.half_extents[1] * self.scale[1]).abs()]
+	}
+}
 
-		let collision = get_collision(&obj_b, &obj_a);
-		eq(collision.unwrap().0, [0.41421348, 0.41421348]);
+impl Shape for AabbObject {
+	fn get_type(&self) -> ShapeType {
+		ShapeType::Aabb
 	}
 
-	fn eq(a: Vector2<f32>, b: Vector2<f32>) {
-		assert!((a[0] - b[0]).abs() < 0.000001);
-		assert!((a[1] - b[1]).abs() < 0.000001);
+	fn set_position(&mut self, position: Vector2<f32>) {
+		self.position = position;
+	}
+
+	fn get_position(&self) -> Vector2<f32> {
+		self.position
+	}
+
+	// Always axis aligned
+	fn set_rotation(&mut self, _rotation: f32) {
+	}
+
+	fn get_rotation(&self) -> f32 {
+		0.0
+	}
+
+	fn set_scale(&mut self, scale: Vector2<f32>) {
+		self.scale = scale;
+	}
+
+	fn get_scale(&self) -> Vector2<f32> {
+		self.scale
+	}
+
+	// Clock wise, like the other boxes
+	fn visit_verts(&self, visit: &mut dyn FnMut(&[Vector2<f32>])) {
+		let [x, y] = self.get_scaled_half_extents();
+		let corner = |offset: Vector2<f32>| vec2_add(offset, self.position);
+		visit(&[corner([-x, y]), corner([x, y]), corner([x, -y]), corner([-x, -y])]);
+	}
+
+	fn visit_normals_to_test_against(&self, _other_shape: &dyn Shape, visit: &mut dyn FnMut(Vector2<f32>)) {
+		visit([1.0, 0.0]);
+		visit([0.0, 1.0]);
+	}
+
+	fn get_support(&self, direction: Vector2<f32>) -> Vector2<f32> {
+		let half_extents = self.get_scaled_half_extents();
+		let x = if direction[0] < 0.0 { -half_extents[0] } else { half_extents[0] };
+		let y = if direction[1] < 0.0 { -half_extents[1] } else { half_extents[1] };
+		vec2_add(self.position, [x, y])
+	}
+
+	fn calculate_projection_on(&self, normal: &Vector2<f32>) -> Interval {
+		let half_extents = self.get_scaled_half_extents();
+		let center = vec2_dot(*normal, self.position);
+		let extent = (normal[0] * half_extents[0]).abs() + (normal[1] * half_extents[1]).abs();
+		[center - extent, center + extent]
+	}
+
+	fn get_aabb(&self) -> Aabb {
+		let half_extents = self.get_scaled_half_extents();
+		Aabb::new(vec2_sub(self.position, half_extents), vec2_add(self.position, half_extents))
+	}
+}
+
+// Connected segments, for open or curved arena boundaries. It is not convex,
+// every segment collides on its own and the deepest one wins. Like ConvexObject, the
+// setters keep the world space points and segments cached
+#[derive(Debug)]
+pub struct ChainObject {
+	position: Vector2<f32>,
+	// Relative to the position
+	verts: Vec<Vector2<f32>>,
+	// Also joins the last point back to the first
+	closed: bool,
+	scale: Vector2<f32>,
+	rotation: f32,
+
+	world_verts: Vec<Vector2<f32>>,
+	segments: Vec<SegmentObject>,
+}
+
+impl ChainObject {
+	pub fn new(position: Vector2<f32>, verts: Vec<Vector2<f32>>, closed: bool) -> Self {
+		assert!(verts.len() >= 2);
+		// Room for closing the chain later
+		let mut result = ChainObject {
+			position,
+			world_verts: Vec::with_capacity(verts.len() + 1),
+			segments: Vec::with_capacity(verts.len()),
+			verts,
+			closed,
+			scale: [1.0, 1.0],
+			rotation: 0.0,
+		};
+		result.update_world_verts();
+		result
+	}
+
+	pub fn is_closed(&self) -> bool {
+		self.closed
+	}
+
+	pub fn set_closed(&mut self, closed: bool) {
+		if closed != self.closed {
+			self.closed = closed;
+			self.update_world_verts();
+		}
+	}
+
+	fn update_world_verts(&mut self) {
+		let (position, scale, rotation) = (self.position, self.scale, self.rotation);
+		self.world_verts.clear();
+		self.world_verts.extend(self.verts.iter().map(|vert| to_world(*vert, position, scale, rotation)));
+		if self.closed {
+			let first = self.world_verts[0];
+			self.world_verts.push(first);
+		}
+		self.segments.clear();
+		self.segments.extend(self.world_verts.windows(2).map(|ends| SegmentObject::new(ends[0], ends[1])));
+	}
+}
+
+impl Shape for ChainObject {
+	fn get_type(&self) -> ShapeType {
+		ShapeType::Chain
+	}
+
+	fn set_position(&mut self, position: Vector2<f32>) {
+		if position != self.position {
+			self.position = position;
+			self.update_world_verts();
+		}
+	}
+
+	fn get_position(&self) -> Vector2<f32> {
+		self.position
+	}
+
+	fn set_rotation(&mut self, rotation: f32) {
+		if rotation != self.rotation {
+			self.rotation = rotation;
+			self.update_world_verts();
+		}
+	}
+
+	fn get_rotation(&self) -> f32 {
+		self.rotation
+	}
+
+	fn set_scale(&mut self, scale: Vector2<f32>) {
+		if scale != self.scale {
+			self.scale = scale;
+			self.update_world_verts();
+		}
+	}
+
+	fn get_scale(&self) -> Vector2<f32> {
+		self.scale
+	}
+
+	fn visit_verts(&self, visit: &mut dyn FnMut(&[Vector2<f32>])) {
+		visit(&self.world_verts);
+	}
+
+	fn get_segments(&self) -> &[SegmentObject] {
+		&self.segments
+	}
+
+	fn visit_normals_to_test_against(&self, _other_shape: &dyn Shape, visit: &mut dyn FnMut(Vector2<f32>)) {
+		for ends in self.world_verts.windows(2) {
+			visit(calculate_normal(ends[0], ends[1]));
+		}
+	}
+
+	fn calculate_projection_on(&self, normal: &Vector2<f32>) -> Interval {
+		project_points(&self.world_verts, normal)
+	}
+
+	fn get_aabb(&self) -> Aabb {
+		Aabb::from_points(&self.world_verts)
+	}
+}
+
+// Any simple polygon, concave ones included. Collides as the convex pieces it is split into.
+// The setters keep the outline and the pieces in world space
+#[derive(Debug)]
+pub struct CompoundObject {
+	position: Vector2<f32>,
+	// Outline and pieces are clock wise, relative to the position
+	verts: Vec<Vector2<f32>>,
+	scale: Vector2<f32>,
+	rotation: f32,
+
+	world_verts: Vec<Vector2<f32>>,
+	// Share the transform of the whole shape
+	pieces: Vec<ConvexObject>,
+}
+
+impl CompoundObject {
+	// Either winding is accepted
+	pub fn new(position: Vector2<f32>, verts: &[Vector2<f32>]) -> Result<Self, PolygonError> {
+		let verts = validate_polygon(verts)?;
+		let pieces = decompose(&verts).into_iter().map(|piece| ConvexObject::new(position, piece)).collect();
+		let mut result = CompoundObject {
+			position,
+			world_verts: verts.clone(),
+			verts,
+			scale: [1.0, 1.0],
+			rotation: 0.0,
+			pieces,
+		};
+		result.update_world_verts();
+		Ok(result)
+	}
+
+	pub fn get_piece_count(&self) -> usize {
+		self.pieces.len()
+	}
+
+	fn update_world_verts(&mut self) {
+		for (world, vert) in self.world_verts.iter_mut().zip(&self.verts) {
+			*world = to_world(*vert, self.position, self.scale, self.rotation);
+		}
+	}
+}
+
+impl Shape for CompoundObject {
+	fn get_type(&self) -> ShapeType {
+		ShapeType::Compound
+	}
+
+	fn set_position(&mut self, position: Vector2<f32>) {
+		if position != self.position {
+			self.position = position;
+			self.update_world_verts();
+			for piece in &mut self.pieces {
+				piece.set_position(position);
+			}
+		}
+	}
+
+	fn get_position(&self) -> Vector2<f32> {
+		self.position
+	}
+
+	fn set_rotation(&mut self, rotation: f32) {
+		if rotation != self.rotation {
+			self.rotation = rotation;
+			self.update_world_verts();
+			for piece in &mut self.pieces {
+				piece.set_rotation(rotation);
+			}
+		}
+	}
+
+	fn get_rotation(&self) -> f32 {
+		self.rotation
+	}
+
+	fn set_scale(&mut self, scale: Vector2<f32>) {
+		if scale != self.scale {
+			self.scale = scale;
+			self.update_world_verts();
+			for piece in &mut self.pieces {
+				piece.set_scale(scale);
+			}
+		}
+	}
+
+	fn get_scale(&self) -> Vector2<f32> {
+		self.scale
+	}
+
+	// The outline
+	fn visit_verts(&self, visit: &mut dyn FnMut(&[Vector2<f32>])) {
+		visit(&self.world_verts);
+	}
+
+	fn get_pieces(&self) -> &[ConvexObject] {
+		&self.pieces
+	}
+
+	fn visit_normals_to_test_against(&self, _other_shape: &dyn Shape, visit: &mut dyn FnMut(Vector2<f32>)) {
+		let verts = &self.world_verts;
+		for i in 0..verts.len() {
+			visit(calculate_normal(verts[i], verts[(i + 1) % verts.len()]));
+		}
+	}
+
+	fn calculate_projection_on(&self, normal: &Vector2<f32>) -> Interval {
+		project_points(&self.world_verts, normal)
+	}
+
+	fn get_aabb(&self) -> Aabb {
+		Aabb::from_points(&self.world_verts)
+	}
+}
+
+// minimum displacement and normal
+pub type CollistionInfo = Option<(Vector2<f32>, Vector2<f32>)>;
+
+// How get_collision_with tests a pair of convex shapes
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum NarrowPhase {
+	// Separating axes, see get_normals_to_test_against
+	#[default]
+	Sat,
+	// GJK and EPA, see get_support
+	Gjk,
+}
+
+pub fn get_collision(a: &dyn Shape, b: &dyn Shape) -> CollistionInfo {
+	get_collision_with(a, b, NarrowPhase::Sat)
+}
+
+pub fn get_collision_with(a: &dyn Shape, b: &dyn Shape, narrow_phase: NarrowPhase) -> CollistionInfo {
+	// Chains are not convex, they collide through their deepest segment
+	if a.get_type() == ShapeType::Chain {
+		return deepest_collision(a.get_segments().iter().map(|segment| get_collision_with(segment, b, narrow_phase)));
+	}
+	if b.get_type() == ShapeType::Chain {
+		return deepest_collision(b.get_segments().iter().map(|segment| get_collision_with(a, segment, narrow_phase)));
+	}
+	if b.get_type() == ShapeType::Compound {
+		return compound_collision(a, b.get_pieces(), narrow_phase);
+	}
+	if a.get_type() == ShapeType::Compound {
+		return get_collision_with(b, a, narrow_phase).map(|(disp, normal)| (vec2_neg(disp), vec2_neg(normal)));
+	}
+	match narrow_phase {
+		NarrowPhase::Sat => get_collision_sat(a, b),
+		NarrowPhase::Gjk => get_collision_gjk(a, b),
+	}
+}
+
+fn get_collision_sat(a: &dyn Shape, b: &dyn Shape) -> CollistionInfo {
+	let mut min_overlap_len = f32::MAX;
+	let mut min_overlap_vec = [0.0, 0.0];
+	let mut separated = false;
+	let mut test_axis = |normal: Vector2<f32>| {
+		if separated {
+			return;
+		}
+		let proj_a = a.calculate_projection_on(&normal);
+		let proj_b = b.calculate_projection_on(&normal);
+		if calculate_overlap(&proj_a, &proj_b).is_none() {
+			separated = true;
+			return;
+		}
+		// The shorter way out for a, a segment has no width so the shared part of the
+		// projections alone would not push it out of the shape it lies in
+		let backwards = proj_a[1] - proj_b[0];
+		let forwards = proj_b[1] - proj_a[0];
+		let lenght = min(backwards, forwards);
+		if lenght < min_overlap_len {
+			min_overlap_len = lenght;
+			min_overlap_vec = if backwards < forwards {
+				vec2_scale(normal, -lenght)
+			} else {
+				vec2_scale(normal, lenght)
+			};
+		}
+	};
+	let mut tested = false;
+	{
+		let mut visit = |normal: Vector2<f32>| {
+			tested = true;
+			test_axis(normal);
+		};
+		a.visit_normals_to_test_against(b, &mut visit);
+		b.visit_normals_to_test_against(a, &mut visit);
+	}
+	// Round shapes sharing a center have no axis between them, any way out is as good as another
+	if !tested {
+		test_axis([1.0, 0.0]);
+	}
+	if separated {
+		return None;
+	}
+	Some((min_overlap_vec, vec2_normalized(min_overlap_vec)))
+}
+
+fn deepest_collision<I: Iterator<Item = CollistionInfo>>(collisions: I) -> CollistionInfo {
+	collisions.fold(None, |deepest, collision| match (deepest, collision) {
+		(Some(best), Some(current)) if vec2_square_len(current.0) > vec2_square_len(best.0) => collision,
+		(None, _) => collision,
+		_ => deepest,
+	})
+}
+
+// Pushing out of one piece can push into its neighbour, the shortest push that clears
+// every piece is the one out of the whole shape. In an inside corner that takes the
+// pushes out of both walls together
+fn compound_collision(shape: &dyn Shape, pieces: &[ConvexObject], narrow_phase: NarrowPhase) -> CollistionInfo {
+	let collide = |piece: &ConvexObject| get_collision_with(shape, piece, narrow_phase).map(|(disp, _)| disp);
+	let mut shortest: Option<Vector2<f32>> = None;
+	let mut deepest: Option<Vector2<f32>> = None;
+	{
+		// Trying the move with the pieces in place, so nothing has to be copied
+		let mut consider = |disp: Vector2<f32>| {
+			if vec2_square_len(disp) == 0.0 || shortest.is_some_and(|shortest| vec2_square_len(disp) >= vec2_square_len(shortest)) {
+				return;
+			}
+			if pieces.iter().all(|piece| get_moved_distance_gjk(shape, piece, disp) > -CONTACT_TOLERANCE) {
+				shortest = Some(disp);
+			}
+		};
+		// Single pushes win ties, then pairs of them. Collisions are tested again rather than stored
+		for piece in pieces {
+			if let Some(disp) = collide(piece) {
+				if deepest.is_none_or(|deepest| vec2_square_len(disp) > vec2_square_len(deepest)) {
+					deepest = Some(disp);
+				}
+				consider(disp);
+			}
+		}
+		for (i, first) in pieces.iter().enumerate() {
+			if let Some(first) = collide(first) {
+				for second in pieces[i + 1..].iter().filter_map(&collide) {
+					consider(vec2_add(first, second));
+				}
+			}
+		}
+	}
+	// Wedged in too deep, the deepest piece wins like for chains
+	let disp = shortest.or(deepest)?;
+	Some((disp, vec2_normalized(disp)))
+}
+
+// How far apart two shapes are, see get_distance
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ShapeDistance {
+	// Negative by the depth when the shapes overlap
+	pub distance: f32,
+	// Nearest points on the surface of each shape, world space. When overlapping, the
+	// deepest points of each inside the other
+	pub point_a: Vector2<f32>,
+	pub point_b: Vector2<f32>,
+	// From b towards a, the same as the get_collision normal when overlapping.
+	// point_a is point_b + normal * distance
+	pub normal: Vector2<f32>,
+}
+
+// Signed distance and nearest points between two shapes. Chains and compounds measure to
+// their nearest part, so inside a compound's corner the depth can be less than get_collision's
+pub fn get_distance(a: &dyn Shape, b: &dyn Shape) -> ShapeDistance {
+	match (a.get_type(), b.get_type()) {
+		(ShapeType::Chain, _) => nearest_distance(a.get_segments().iter().map(|segment| get_distance(segment, b))),
+		(_, ShapeType::Chain) => nearest_distance(b.get_segments().iter().map(|segment| get_distance(a, segment))),
+		(ShapeType::Compound, _) => nearest_distance(a.get_pieces().iter().map(|piece| get_distance(piece, b))),
+		(_, ShapeType::Compound) => nearest_distance(b.get_pieces().iter().map(|piece| get_distance(a, piece))),
+		_ => get_distance_gjk(a, b),
+	}
+}
+
+fn nearest_distance<I: Iterator<Item = ShapeDistance>>(distances: I) -> ShapeDistance {
+	distances.fold(None, |nearest: Option<ShapeDistance>, current| {
+		if nearest.is_none_or(|nearest| current.distance < nearest.distance) { Some(current) } else { nearest }
+	}).expect("chains and compounds have parts")
+}
+
+// Where two overlapping shapes touch
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ContactManifold {
+	// Same as the get_collision normal, pushes a out of b
+	pub normal: Vector2<f32>,
+	// Along the normal
+	pub depth: f32,
+	points: [Vector2<f32>; 2],
+	point_count: usize,
+}
+
+impl ContactManifold {
+	// One or two points, the deepest ones of a shape inside the other. World space
+	pub fn get_points(&self) -> &[Vector2<f32>] {
+		&self.points[..self.point_count]
+	}
+
+	fn new(normal: Vector2<f32>, depth: f32, points: &[Vector2<f32>]) -> Self {
+		let mut result = ContactManifold { normal, depth, points: [[0.0, 0.0]; 2], point_count: points.len() };
+		result.points[..points.len()].copy_from_slice(points);
+		result
+	}
+}
+
+pub fn get_contact_manifold(a: &dyn Shape, b: &dyn Shape) -> Option<ContactManifold> {
+	// The same segment get_collision picks
+	if a.get_type() == ShapeType::Chain {
+		return deepest_manifold(a.get_segments().iter().map(|segment| get_contact_manifold(segment, b)));
+	}
+	if b.get_type() == ShapeType::Chain {
+		return deepest_manifold(b.get_segments().iter().map(|segment| get_contact_manifold(a, segment)));
+	}
+	if b.get_type() == ShapeType::Compound {
+		// The points of the piece pushing the same way as the whole shape
+		let (disp, normal) = get_collision(a, b)?;
+		let manifold = b.get_pieces().iter()
+			.filter_map(|piece| get_contact_manifold(a, piece))
+			.fold(None, |best: Option<ContactManifold>, manifold| {
+				if best.is_none_or(|best| vec2_dot(manifold.normal, normal) > vec2_dot(best.normal, normal)) { Some(manifold) } else { best }
+			})?;
+		return Some(ContactManifold::new(normal, vec2_len(disp), manifold.get_points()));
+	}
+	if a.get_type() == ShapeType::Compound {
+		let manifold = get_contact_manifold(b, a)?;
+		return Some(ContactManifold::new(vec2_neg(manifold.normal), manifold.depth, manifold.get_points()));
+	}
+
+	let (disp, normal) = get_collision(a, b)?;
+	let depth = vec2_len(disp);
+	let mut result = None;
+	a.visit_verts(&mut |verts_a| b.visit_verts(&mut |verts_b| {
+		// The deepest points of a rounded shape, or the only point of a segment of zero length
+		result = Some(if a.get_radius() > 0.0 || is_point(verts_a) {
+			rounded_contacts(verts_a, a.get_radius(), vec2_neg(normal), normal, depth)
+		} else if b.get_radius() > 0.0 || is_point(verts_b) {
+			rounded_contacts(verts_b, b.get_radius(), normal, normal, depth)
+		} else {
+			clip_convex_contacts(verts_a, verts_b, normal, depth)
+		});
+	}));
+	result
+}
+
+fn deepest_manifold<I: Iterator<Item = Option<ContactManifold>>>(manifolds: I) -> Option<ContactManifold> {
+	manifolds.fold(None, |deepest, manifold| match (deepest, manifold) {
+		(Some(best), Some(current)) if current.depth > best.depth => manifold,
+		(None, _) => manifold,
+		_ => deepest,
+	})
+}
+
+// Ends of the core furthest along direction, both when a capsule lies flat against the other shape.
+// Rounded shapes have at most two verts
+fn rounded_contacts(verts: &[Vector2<f32>], radius: f32, direction: Vector2<f32>, normal: Vector2<f32>, depth: f32) -> ContactManifold {
+	let furthest = project_points(verts, &direction)[1];
+	let mut result = ContactManifold::new(normal, depth, &[]);
+	for vert in verts.iter().filter(|vert| vec2_dot(**vert, direction) >= furthest - CONTACT_TOLERANCE) {
+		let point = vec2_add(*vert, vec2_scale(direction, radius));
+		// Both ends of a capsule of zero length
+		if result.point_count < result.points.len() && !result.get_points().contains(&point) {
+			result.points[result.point_count] = point;
+			result.point_count += 1;
+		}
+	}
+	result
+}
+
+fn is_point(verts: &[Vector2<f32>]) -> bool {
+	verts.iter().all(|vert| *vert == verts[0])
+}
+
+// Edge of a polygon facing direction, from a to b in the winding order
+struct Edge {
+	// The vertex furthest along the direction
+	deepest: Vector2<f32>,
+	a: Vector2<f32>,
+	b: Vector2<f32>,
+}
+
+fn facing_edge(verts: &[Vector2<f32>], direction: Vector2<f32>) -> Edge {
+	let count = verts.len();
+	let mut index = 0;
+	for i in 1..count {
+		if vec2_dot(verts[i], direction) > vec2_dot(verts[index], direction) {
+			index = i;
+		}
+	}
+	let deepest = verts[index];
+	let next = verts[(index + 1) % count];
+	let prev = verts[(index + count - 1) % count];
+	// Of the two edges at the vertex, the one closer to perpendicular to the direction
+	let to_next = vec2_normalized(vec2_sub(deepest, next));
+	let to_prev = vec2_normalized(vec2_sub(deepest, prev));
+	if vec2_dot(to_prev, direction) <= vec2_dot(to_next, direction) {
+		Edge { deepest, a: prev, b: deepest }
+	} else {
+		Edge { deepest, a: deepest, b: next }
+	}
+}
+
+// Clips the incident edge against the sides of the reference edge, see
+// http://www.dyn4j.org/2011/11/contact-points-using-clipping/
+fn clip_convex_contacts(verts_a: &[Vector2<f32>], verts_b: &[Vector2<f32>], normal: Vector2<f32>, depth: f32) -> ContactManifold {
+	// The normal points from b to a
+	let edge_a = facing_edge(verts_a, vec2_neg(normal));
+	let edge_b = facing_edge(verts_b, normal);
+	let along_a = vec2_dot(vec2_normalized(vec2_sub(edge_a.b, edge_a.a)), normal).abs();
+	let along_b = vec2_dot(vec2_normalized(vec2_sub(edge_b.b, edge_b.a)), normal).abs();
+	// The reference edge is the one closer to perpendicular to the normal, it faces the incident shape
+	let (reference, incident, towards_incident) = if along_a <= along_b {
+		(edge_a, edge_b, vec2_neg(normal))
+	} else {
+		(edge_b, edge_a, normal)
+	};
+
+	let tangent = vec2_normalized(vec2_sub(reference.b, reference.a));
+	let mut points = [incident.a, incident.b];
+	let mut count = clip(&mut points, tangent, vec2_dot(tangent, reference.a));
+	if count == 2 {
+		count = clip(&mut points, vec2_neg(tangent), -vec2_dot(tangent, reference.b));
+	}
+	if count < 2 {
+		return ContactManifold::new(normal, depth, &[incident.deepest]);
+	}
+
+	// Only points inside the reference shape are in contact
+	let mut face_normal = [-tangent[1], tangent[0]];
+	if vec2_dot(face_normal, towards_incident) < 0.0 {
+		face_normal = vec2_neg(face_normal);
+	}
+	let face = vec2_dot(face_normal, reference.deepest);
+	let mut inside = [[0.0, 0.0]; 2];
+	let mut count = 0;
+	for point in &points {
+		if vec2_dot(face_normal, *point) <= face + CONTACT_TOLERANCE {
+			inside[count] = *point;
+			count += 1;
+		}
+	}
+	if count == 0 {
+		return ContactManifold::new(normal, depth, &[incident.deepest]);
+	}
+	ContactManifold::new(normal, depth, &inside[..count])
+}
+
+// Keeps the part of the segment at or beyond offset along direction, returns the points left
+fn clip(points: &mut [Vector2<f32>; 2], direction: Vector2<f32>, offset: f32) -> usize {
+	let d1 = vec2_dot(direction, points[0]) - offset;
+	let d2 = vec2_dot(direction, points[1]) - offset;
+	if d1 >= 0.0 && d2 >= 0.0 {
+		return 2;
+	}
+	if d1 < 0.0 && d2 < 0.0 {
+		return 0;
+	}
+	let crossing = vec2_add(points[0], vec2_scale(vec2_sub(points[1], points[0]), d1 / (d1 - d2)));
+	if d1 < 0.0 {
+		points[0] = crossing;
+	} else {
+		points[1] = crossing;
+	}
+	2
+}
+
+// First contact of a moving circle
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TimeOfImpact {
+	// Fraction of the motion travelled before touching, 0 to 1
+	pub time: f32,
+	// Points from the other shape towards the circle
+	pub normal: Vector2<f32>,
+}
+
+// Sweeps the circle along motion while the other shape moves along other_motion.
+// Shapes that already overlap at the start are left to get_collision
+pub fn get_time_of_impact(circle: &CircleObject, motion: Vector2<f32>, other: &dyn Shape, other_motion: Vector2<f32>) -> Option<TimeOfImpact> {
+	let motion = vec2_sub(motion, other_motion);
+	if get_collision(circle, other).is_some() {
+		return None;
+	}
+	if other.get_type() == ShapeType::Chain {
+		return other.get_segments().iter()
+			.filter_map(|segment| sweep_circle(circle.position, circle.get_scaled_radius(), motion, &segment.get_ends()))
+			.fold(None, |first: Option<TimeOfImpact>, toi| if first.is_none_or(|first| toi.time < first.time) { Some(toi) } else { first });
+	}
+	if other.get_type() == ShapeType::Compound {
+		return other.get_pieces().iter()
+			.filter_map(|piece| sweep_circle(circle.position, circle.get_scaled_radius(), motion, &piece.world_verts))
+			.fold(None, |first: Option<TimeOfImpact>, toi| if first.is_none_or(|first| toi.time < first.time) { Some(toi) } else { first });
+	}
+	let mut result = None;
+	other.visit_verts(&mut |verts| result = sweep_circle(circle.position, circle.get_scaled_radius() + other.get_radius(), motion, verts));
+	result
+}
+
+pub fn get_time_of_impact_circles(a: &CircleObject, motion_a: Vector2<f32>, b: &CircleObject, motion_b: Vector2<f32>) -> Option<TimeOfImpact> {
+	let radius = a.get_scaled_radius() + b.get_scaled_radius();
+	sweep_circle_circle(a.position, radius, vec2_sub(motion_a, motion_b), b.position)
+}
+
+// Circle of the given radius against a static point
+fn sweep_circle_circle(position: Vector2<f32>, radius: f32, motion: Vector2<f32>, other: Vector2<f32>) -> Option<TimeOfImpact> {
+	let offset = vec2_sub(position, other);
+	if vec2_square_len(offset) <= radius * radius {
+		return None;
+	}
+	let time = solve_sweep(offset, radius, motion)?;
+	let normal = vec2_normalized(vec2_add(offset, vec2_scale(motion, time)));
+	Some(TimeOfImpact { time, normal })
+}
+
+// Against a point, a segment or a convex polygon, grown by the radius. Does not check
+// whether the circle starts inside
+fn sweep_circle(position: Vector2<f32>, radius: f32, motion: Vector2<f32>, verts: &[Vector2<f32>]) -> Option<TimeOfImpact> {
+	if vec2_square_len(motion) == 0.0 {
+		return None;
+	}
+	let center = polygon_center(verts);
+	let mut result: Option<TimeOfImpact> = None;
+	let mut consider = |time: f32, normal: Vector2<f32>| {
+		if result.is_none_or(|best| time < best.time) {
+			result = Some(TimeOfImpact { time, normal });
+		}
+	};
+
+	for i in 0..verts.len() {
+		let a = verts[i];
+		let b = verts[(i + 1) % verts.len()];
+		// A segment gets both of its sides, one from each direction
+		if verts.len() > 1 {
+			let normal = outward_normal(a, b, center);
+
+			// The edge pushed out by the radius, hit from the outside only
+			let distance = vec2_dot(vec2_sub(position, a), normal);
+			let speed = vec2_dot(motion, normal);
+			if speed < 0.0 && distance >= radius {
+				let time = (radius - distance) / speed;
+				if time <= 1.0 {
+					let contact = vec2_sub(vec2_add(position, vec2_scale(motion, time)), vec2_scale(normal, radius));
+					let edge = vec2_sub(b, a);
+					let along = vec2_dot(vec2_sub(contact, a), edge) / vec2_square_len(edge);
+					if (0.0..=1.0).contains(&along) {
+						consider(time, normal);
+					}
+				}
+			}
+		}
+
+		// Rounded corner
+		let offset = vec2_sub(position, a);
+		if let Some(time) = solve_sweep(offset, radius, motion) {
+			let normal = vec2_add(offset, vec2_scale(motion, time));
+			// Without a radius a corner hit dead on has nothing to point along
+			let normal = if vec2_square_len(normal) > 0.0 { normal } else { vec2_neg(motion) };
+			consider(time, vec2_normalized(normal));
+		}
+	}
+	result
+}
+
+// Smallest time in 0..1 at which offset + motion * time is radius long, the offset starts longer
+fn solve_sweep(offset: Vector2<f32>, radius: f32, motion: Vector2<f32>) -> Option<f32> {
+	let a = vec2_square_len(motion);
+	let b = 2.0 * vec2_dot(offset, motion);
+	let c = vec2_square_len(offset) - radius * radius;
+	if a == 0.0 || c < 0.0 || b >= 0.0 {
+		return None;
+	}
+	let discriminant = b * b - 4.0 * a * c;
+	if discriminant < 0.0 {
+		return None;
+	}
+	let time = (-b - discriminant.sqrt()) / (2.0 * a);
+	if (0.0..=1.0).contains(&time) {
+		Some(time)
+	} else {
+		None
+	}
+}
+
+// First point a ray or a cast circle touches
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+	// Along the direction, from the origin
+	pub distance: f32,
+	pub point: Vector2<f32>,
+	// Surface normal at the point, facing the origin
+	pub normal: Vector2<f32>,
+}
+
+// direction must be normalized. Rays starting inside a shape do not hit it, but the inside of a chain is open
+pub fn raycast(shape: &dyn Shape, origin: Vector2<f32>, direction: Vector2<f32>, max_distance: f32) -> Option<RayHit> {
+	debug_assert!((vec2_len(direction) - 1.0).abs() < 0.001, "direction must be normalized");
+	let (distance, normal) = match shape.get_type() {
+		ShapeType::Chain => {
+			return shape.get_segments().iter()
+				.filter_map(|segment| raycast(segment, origin, direction, max_distance))
+				.fold(None, |first: Option<RayHit>, hit| if first.is_none_or(|first| hit.distance < first.distance) { Some(hit) } else { first });
+		}
+		ShapeType::Compound => {
+			let pieces = shape.get_pieces();
+			// Leaving one piece enters its neighbour, which is not a hit
+			if pieces.iter().any(|piece| contains_point(&piece.world_verts, origin)) {
+				return None;
+			}
+			return pieces.iter()
+				.filter_map(|piece| raycast(piece, origin, direction, max_distance))
+				.fold(None, |first: Option<RayHit>, hit| if first.is_none_or(|first| hit.distance < first.distance) { Some(hit) } else { first });
+		}
+		ShapeType::Convex | ShapeType::Aabb => {
+			let mut result = None;
+			shape.visit_verts(&mut |verts| {
+				result = raycast_convex(verts, origin, direction, max_distance)
+					.map(|distance| (distance, closest_edge_normal(verts, vec2_add(origin, vec2_scale(direction, distance)))));
+			});
+			result?
+		}
+		// A point moving towards a point or a segment grown by the radius
+		ShapeType::Circle | ShapeType::Capsule | ShapeType::Segment => {
+			let mut result = None;
+			shape.visit_verts(&mut |verts| result = sweep_circle(origin, shape.get_radius(), vec2_scale(direction, max_distance), verts));
+			let toi = result?;
+			(toi.time * max_distance, toi.normal)
+		}
+	};
+	let point = vec2_add(origin, vec2_scale(direction, distance));
+	Some(RayHit { distance, point, normal })
+}
+
+// Moves a circle of the given radius from origin along direction, the point is where it touches the shape.
+// direction must be normalized
+pub fn circle_cast(shape: &dyn Shape, origin: Vector2<f32>, radius: f32, direction: Vector2<f32>, max_distance: f32) -> Option<RayHit> {
+	debug_assert!((vec2_len(direction) - 1.0).abs() < 0.001, "direction must be normalized");
+	let circle = CircleObject::new(origin, radius);
+	let toi = get_time_of_impact(&circle, vec2_scale(direction, max_distance), shape, [0.0, 0.0])?;
+	let distance = toi.time * max_distance;
+	let center = vec2_add(origin, vec2_scale(direction, distance));
+	Some(RayHit { distance, point: vec2_sub(center, vec2_scale(toi.normal, radius)), normal: toi.normal })
+}
+
+// Clips the ray against every edge, see Cyrus-Beck
+fn raycast_convex(verts: &[Vector2<f32>], origin: Vector2<f32>, direction: Vector2<f32>, max_distance: f32) -> Option<f32> {
+	let center = polygon_center(verts);
+	let mut enter = f32::MIN;
+	let mut exit = max_distance;
+	for i in 0..verts.len() {
+		let normal = outward_normal(verts[i], verts[(i + 1) % verts.len()], center);
+		let distance = vec2_dot(vec2_sub(origin, verts[i]), normal);
+		let speed = vec2_dot(direction, normal);
+		if speed == 0.0 {
+			// Parallel and outside of this edge
+			if distance > 0.0 {
+				return None;
+			}
+			continue;
+		}
+		let time = -distance / speed;
+		if speed < 0.0 {
+			enter = max(enter, time);
+		} else {
+			exit = min(exit, time);
+		}
+		if enter > exit {
+			return None;
+		}
+	}
+	if enter >= 0.0 && enter <= exit {
+		Some(enter)
+	} else {
+		None
+	}
+}
+
+fn closest_edge_normal(verts: &[Vector2<f32>], point: Vector2<f32>) -> Vector2<f32> {
+	let center = polygon_center(verts);
+	let mut result = [0.0, 0.0];
+	let mut closest = f32::MAX;
+	for i in 0..verts.len() {
+		let normal = outward_normal(verts[i], verts[(i + 1) % verts.len()], center);
+		let distance = vec2_dot(vec2_sub(point, verts[i]), normal).abs();
+		if distance < closest {
+			closest = distance;
+			result = normal;
+		}
+	}
+	result
+}
+
+// On the edge counts as inside
+fn contains_point(verts: &[Vector2<f32>], point: Vector2<f32>) -> bool {
+	let center = polygon_center(verts);
+	(0..verts.len()).all(|i| {
+		let normal = outward_normal(verts[i], verts[(i + 1) % verts.len()], center);
+		vec2_dot(vec2_sub(point, verts[i]), normal) <= 0.0
+	})
+}
+
+fn polygon_center(verts: &[Vector2<f32>]) -> Vector2<f32> {
+	vec2_scale(verts.iter().fold([0.0, 0.0], |sum, vert| vec2_add(sum, *vert)), 1.0 / verts.len() as f32)
+}
+
+// Scale can flip the winding, so the side is decided by the center
+fn outward_normal(a: Vector2<f32>, b: Vector2<f32>, center: Vector2<f32>) -> Vector2<f32> {
+	let normal = calculate_normal(a, b);
+	if vec2_dot(normal, vec2_sub(a, center)) < 0.0 {
+		vec2_neg(normal)
+	} else {
+		normal
+	}
+}
+
+fn support_point(points: &[Vector2<f32>], direction: Vector2<f32>) -> Vector2<f32> {
+	let mut result = points[0];
+	for point in &points[1..] {
+		if vec2_dot(*point, direction) > vec2_dot(result, direction) {
+			result = *point;
+		}
+	}
+	result
+}
+
+// Local to world space: scale, rotate, then offset by position
+fn to_world(vector: Vector2<f32>, position: Vector2<f32>, scale: Vector2<f32>, rotation: f32) -> Vector2<f32> {
+	vec2_add(rotate(vec2_mul(vector, scale), rotation), position)
+}
+
+fn project_points(points: &[Vector2<f32>], normal: &Vector2<f32>) -> Interval {
+	let mut result = [f32::MAX, f32::MIN];
+	for point in points {
+		let dot = vec2_dot(*normal, *point);
+		result = [min(result[0], dot), max(result[1], dot)];
+	}
+	result
+}
+
+// The normal and the direction, two segments lying on the same line are only apart along it
+// None for a segment of zero length, it has no direction
+fn segment_axes(a: Vector2<f32>, b: Vector2<f32>, visit: &mut dyn FnMut(Vector2<f32>)) {
+	if a == b {
+		return;
+	}
+	visit(calculate_normal(a, b));
+	visit(vec2_normalized(vec2_sub(b, a)));
+}
+
+// A rounded shape can be closest to any vertex of the other shape, or to the inside of a
+// segment, which unlike a polygon edge has no normal facing it
+fn rounded_axes(points: &[Vector2<f32>], other_shape: &dyn Shape, visit: &mut dyn FnMut(Vector2<f32>)) {
+	other_shape.visit_verts(&mut |other_verts| {
+		for point in points {
+			let closest = if other_verts.len() == 2 {
+				Some(closest_point_on_segment(*point, other_verts[0], other_verts[1]))
+			} else {
+				None
+			};
+			for target in other_verts.iter().cloned().chain(closest) {
+				let axis = vec2_sub(target, *point);
+				if vec2_square_len(axis) > 0.0 {
+					visit(vec2_normalized(axis));
+				}
+			}
+		}
+	});
+}
+
+fn closest_point_on_segment(point: Vector2<f32>, a: Vector2<f32>, b: Vector2<f32>) -> Vector2<f32> {
+	let edge = vec2_sub(b, a);
+	let length = vec2_square_len(edge);
+	if length == 0.0 {
+		return a;
+	}
+	let along = min(max(vec2_dot(vec2_sub(point, a), edge) / length, 0.0), 1.0);
+	vec2_add(a, vec2_scale(edge, along))
+}
+
+fn rotate(v: Vector2<f32>, angle: f32) -> Vector2<f32> {
+	let (sin, cos) = angle.sin_cos();
+	[v[0] * cos - v[1] * sin, v[0] * sin + v[1] * cos]
+}
+
+fn calculate_normal(a: Vector2<f32>, b: Vector2<f32>) -> Vector2<f32> {
+	let diff = vec2_sub(b, a);
+	vec2_normalized([-diff[1], diff[0]])
+}
+
+fn calculate_overlap(a: &Interval, b: &Interval) -> Option<Interval> {
+	if a[0] < b[1] && a[1] > b[0] {
+		return Some([max(a[0], b[0]), min(a[1], b[1])])
+	}
+	None
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn test_overlap() {
+		eq([1.0, 2.0], calculate_overlap(&[0.0, 2.0], &[1.0, 5.0]).unwrap());
+		eq([1.0, 2.0], calculate_overlap(&[1.0, 5.0], &[0.0, 2.0]).unwrap());
+		assert_eq!(None, calculate_overlap(&[1.0, 2.0], &[3.0, 4.0]));
+		assert_eq!(None, calculate_overlap(&[3.0, 4.0], &[1.0, 2.0]));
+	}
+
+	#[test]
+	fn test_collision_1() {
+		let verts = vec![[-1.0, 1.0], [1.0, 1.0], [1.0, -1.0], [-1.0, -1.0]];
+		let obj_a = ConvexObject::new([0.0, 0.0], verts.clone());
+		let obj_b = ConvexObject::new([1.0, 0.0], verts.clone());
+		let collision = get_collision(&obj_a, &obj_b);
+		eq(collision.unwrap().0, [-1.0, 0.0]);
+
+		let collision = get_collision(&obj_b, &obj_a);
+		eq(collision.unwrap().0, [1.0, 0.0]);
+	}
+
+	#[test]
+	fn test_collision_2() {
+		let verts = vec![[-1.0, 1.0], [1.0, 1.0], [1.0, -1.0], [-1.0, -1.0]];
+		let obj_a = ConvexObject::new([0.0, 0.0], verts.clone());
+		let obj_b = ConvexObject::new([3.0, 0.0], verts.clone());
+		let collision = get_collision(&obj_a, &obj_b);
+		assert_eq!(collision, None);
+
+		let collision = get_collision(&obj_b, &obj_a);
+		assert_eq!(collision, None);
+	}
+
+	#[test]
+	fn test_collision_3() {
+		let obj_a = CircleObject::new([0.0, 0.0], 3.0);
+		let obj_b = CircleObject::new([3.0, 3.0], 2.0);
+		let collision = get_collision(&obj_a, &obj_b);
+		eq(collision.unwrap().0, [-0.5355338, -0.5355338]);
+
+		let collision = get_collision(&obj_b, &obj_a);
+		eq(collision.unwrap().0, [0.5355338, 0.5355338]);
+	}
+
+	#[test]
+	fn test_collision_4() {
+		let obj_a = CircleObject::new([0.0, 0.0], 3.0);
+		let obj_b = CircleObject::new([4.0, 4.0], 2.0);
+		let collision = get_collision(&obj_a, &obj_b);
+		assert_eq!(collision, None);
+
+		let collision = get_collision(&obj_b, &obj_a);
+		assert_eq!(collision, None);
+	}
+
+	#[test]
+	fn test_collision_5() {
+		let verts = vec![[-1.0, 1.0], [1.0, 1.0], [1.0, -1.0], [-1.0, -1.0]];
+		let obj_a = ConvexObject::new([0.0, 0.0], verts.clone());
+		let obj_b = CircleObject::new([2.0, 0.0], 1.5);
+		let collision = get_collision(&obj_a, &obj_b);
+		eq(collision.unwrap().0, [-0.5, 0.0]);
+
+		let collision = get_collision(&obj_b, &obj_a);
+		eq(collision.unwrap().0, [0.5, 0.0]);
+	}
+
+	#[test]
+	fn test_collision_6() {
+		let verts = vec![[-1.0, 1.0], [1.0, 1.0], [1.0, -1.0], [-1.0, -1.0]];
+		let obj_a = ConvexObject::new([0.0, 0.0], verts.clone());
+		let obj_b = CircleObject::new([2.0, 2.0], 2.0);
+		let collision = get_collision(&obj_a, &obj_b);
+		eq(collision.unwrap().0, [-0.41421348, -0.41421348]);
+
+		let collision = get_collision(&obj_b, &obj_a);
+		eq(collision.unwrap().0, [0.41421348, 0.41421348]);
+	}
+
+	#[test]
+	fn test_collision_rotated_1() {
+		let verts = vec![[-1.0, 1.0], [1.0, 1.0], [1.0, -1.0], [-1.0, -1.0]];
+		let mut obj_a = ConvexObject::new([0.0, 0.0], verts.clone());
+		let obj_b = ConvexObject::new([2.2, 0.0], verts.clone());
+		assert_eq!(get_collision(&obj_a, &obj_b), None);
+
+		obj_a.set_rotation(f32::consts::FRAC_PI_4);
+		let collision = get_collision(&obj_a, &obj_b);
+		eq_eps(collision.unwrap().0, [-0.21421354, 0.0], 0.00001);
+
+		let collision = get_collision(&obj_b, &obj_a);
+		eq_eps(collision.unwrap().0, [0.21421354, 0.0], 0.00001);
+	}
+
+	#[test]
+	fn test_collision_rotated_2() {
+		let verts = vec![[-4.0, 1.0], [4.0, 1.0], [4.0, -1.0], [-4.0, -1.0]];
+		let mut obj_a = ConvexObject::new([0.0, 0.0], verts.clone());
+		let obj_b = CircleObject::new([0.0, 3.5], 1.0);
+		assert_eq!(get_collision(&obj_a, &obj_b), None);
+
+		obj_a.set_rotation(f32::consts::FRAC_PI_2);
+		let collision = get_collision(&obj_a, &obj_b);
+		eq_eps(collision.unwrap().0, [0.0, -1.5], 0.00001);
+	}
+
+	#[test]
+	fn test_rotated_verts_and_normals() {
+		let verts = vec![[-2.0, 1.0], [2.0, 1.0], [2.0, -1.0], [-2.0, -1.0]];
+		let mut obj = ConvexObject::new([1.0, 0.0], verts);
+		obj.set_rotation(f32::consts::FRAC_PI_2);
+
+		let expected = [[0.0, -2.0], [0.0, 2.0], [2.0, 2.0], [2.0, -2.0]];
+		for (vert, expected) in world_verts(&obj).iter().zip(expected.iter()) {
+			eq_eps(*vert, *expected, 0.00001);
+		}
+
+		let expected = [[-1.0, 0.0], [0.0, 1.0], [1.0, 0.0], [0.0, -1.0]];
+		for (normal, expected) in obj.normals.iter().zip(expected.iter()) {
+			eq_eps(*normal, *expected, 0.00001);
+		}
+
+		// The cached verts follow every change of the transform
+		obj.set_position([0.0, 0.0]);
+		obj.set_rotation(0.0);
+		obj.set_scale([0.5, 2.0]);
+		let expected = [[-1.0, 2.0], [1.0, 2.0], [1.0, -2.0], [-1.0, -2.0]];
+		for (vert, expected) in world_verts(&obj).iter().zip(expected.iter()) {
+			eq_eps(*vert, *expected, 0.00001);
+		}
+		let expected = [[0.0, 1.0], [1.0, 0.0], [0.0, -1.0], [-1.0, 0.0]];
+		for (normal, expected) in obj.normals.iter().zip(expected.iter()) {
+			eq_eps(*normal, *expected, 0.00001);
+		}
+	}
+
+	#[test]
+	fn test_collision_scaled_1() {
+		let verts = vec![[-1.0, 1.0], [1.0, 1.0], [1.0, -1.0], [-1.0, -1.0]];
+		let mut obj_a = ConvexObject::new([0.0, 0.0], verts.clone());
+		let obj_b = ConvexObject::new([3.0, 0.0], verts.clone());
+		assert_eq!(get_collision(&obj_a, &obj_b), None);
+
+		obj_a.set_scale([2.5, 1.0]);
+		let collision = get_collision(&obj_a, &obj_b);
+		eq(collision.unwrap().0, [-0.5, 0.0]);
+
+		// Scale is applied in local space, so it does not move the shape
+		obj_a.set_position([-10.0, 0.0]);
+		assert_eq!(get_collision(&obj_a, &obj_b), None);
+	}
+
+	#[test]
+	fn test_collision_scaled_2() {
+		let verts = vec![[-1.0, 1.0], [1.0, 1.0], [1.0, -1.0], [-1.0, -1.0]];
+		let mut obj_a = ConvexObject::new([0.0, 0.0], verts.clone());
+		obj_a.set_scale([3.0, 1.0]);
+		obj_a.set_rotation(f32::consts::FRAC_PI_2);
+		let obj_b = CircleObject::new([0.0, 3.5], 1.0);
+		let collision = get_collision(&obj_a, &obj_b);
+		eq_eps(collision.unwrap().0, [0.0, -0.5], 0.00001);
+
+		let verts = world_verts(&obj_a);
+		eq_eps(verts[0], [-1.0, -3.0], 0.00001);
+		eq_eps(verts[2], [1.0, 3.0], 0.00001);
+	}
+
+	#[test]
+	fn test_collision_scaled_circle() {
+		let mut obj_a = CircleObject::new([0.0, 0.0], 1.0);
+		let obj_b = CircleObject::new([3.0, 0.0], 1.0);
+		assert_eq!(get_collision(&obj_a, &obj_b), None);
+
+		obj_a.set_scale([2.5, 2.5]);
+		let collision = get_collision(&obj_a, &obj_b);
+		eq(collision.unwrap().0, [-0.5, 0.0]);
+
+		obj_a.set_scale([1.0, 2.5]);
+		assert_eq!(obj_a.get_scale(), [2.5, 2.5]);
+	}
+
+	#[test]
+	fn test_distance_1() {
+		let verts = vec![[-1.0, 1.0], [1.0, 1.0], [1.0, -1.0], [-1.0, -1.0]];
+		let obj_a = ConvexObject::new([0.0, 0.0], verts.clone());
+		let obj_b = ConvexObject::new([3.0, 0.0], verts.clone());
+		let result = get_distance(&obj_a, &obj_b);
+		assert!((result.distance - 1.0).abs() < 0.00001);
+		eq_eps(result.normal, [-1.0, 0.0], 0.00001);
+		// Anywhere along the facing edges
+		assert!((result.point_a[0] - 1.0).abs() < 0.00001 && result.point_a[1].abs() <= 1.0);
+		eq_eps(result.point_b, [2.0, result.point_a[1]], 0.00001);
+
+		let result = get_distance(&obj_b, &obj_a);
+		assert!((result.distance - 1.0).abs() < 0.00001);
+		eq_eps(result.normal, [1.0, 0.0], 0.00001);
+	}
+
+	#[test]
+	fn test_distance_2() {
+		// Overlapping by the same depth get_collision finds
+		let verts = vec![[-1.0, 1.0], [1.0, 1.0], [1.0, -1.0], [-1.0, -1.0]];
+		let obj_a = ConvexObject::new([0.0, 0.0], verts.clone());
+		let obj_b = ConvexObject::new([1.0, 0.0], verts.clone());
+		let result = get_distance(&obj_a, &obj_b);
+		assert!((result.distance + 1.0).abs() < 0.00001);
+		eq_eps(result.normal, [-1.0, 0.0], 0.00001);
+		assert!((result.point_a[0] - 1.0).abs() < 0.00001);
+		assert!(result.point_b[0].abs() < 0.00001);
+	}
+
+	#[test]
+	fn test_distance_circles() {
+		let obj_a = CircleObject::new([0.0, 0.0], 3.0);
+		let obj_b = CircleObject::new([6.0, 8.0], 2.0);
+		let result = get_distance(&obj_a, &obj_b);
+		assert!((result.distance - 5.0).abs() < 0.00001);
+		eq_eps(result.normal, [-0.6, -0.8], 0.00001);
+		eq_eps(result.point_a, [1.8, 2.4], 0.00001);
+		eq_eps(result.point_b, [4.8, 6.4], 0.00001);
+
+		let obj_c = CircleObject::new([3.0, 3.0], 2.0);
+		let result = get_distance(&obj_a, &obj_c);
+		eq_eps(vec2_scale(result.normal, -result.distance), [-0.5355338, -0.5355338], 0.00001);
+	}
+
+	#[test]
+	fn test_distance_circle_convex() {
+		let verts = vec![[-1.0, 1.0], [1.0, 1.0], [1.0, -1.0], [-1.0, -1.0]];
+		let obj_a = ConvexObject::new([0.0, 0.0], verts);
+		// Nearest to a corner
+		let obj_b = CircleObject::new([3.0, 3.0], 1.0);
+		let result = get_distance(&obj_a, &obj_b);
+		assert!((result.distance - (8.0f32.sqrt() - 1.0)).abs() < 0.00001);
+		eq_eps(result.point_a, [1.0, 1.0], 0.00001);
+		eq_eps(result.point_b, [3.0 - f32::consts::FRAC_1_SQRT_2, 3.0 - f32::consts::FRAC_1_SQRT_2], 0.00001);
+
+		// Nearest to an edge
+		let obj_c = CircleObject::new([0.5, 4.0], 1.0);
+		let result = get_distance(&obj_c, &obj_a);
+		assert!((result.distance - 2.0).abs() < 0.00001);
+		eq_eps(result.normal, [0.0, 1.0], 0.00001);
+		eq_eps(result.point_a, [0.5, 3.0], 0.00001);
+		eq_eps(result.point_b, [0.5, 1.0], 0.00001);
+	}
+
+	#[test]
+	fn test_contact_manifold_boxes() {
+		let verts = vec![[-1.0, 1.0], [1.0, 1.0], [1.0, -1.0], [-1.0, -1.0]];
+		let obj_a = ConvexObject::new([0.0, 0.0], verts.clone());
+		let obj_b = ConvexObject::new([1.5, 0.5], verts.clone());
+		let manifold = get_contact_manifold(&obj_a, &obj_b).unwrap();
+		eq(manifold.normal, [-1.0, 0.0]);
+		assert!((manifold.depth - 0.5).abs() < 0.000001);
+		// The edge of b inside a, cut to where the edges face each other
+		let mut points = manifold.get_points().to_vec();
+		points.sort_by(|p, q| p[1].partial_cmp(&q[1]).unwrap());
+		assert_eq!(points.len(), 2);
+		eq(points[0], [0.5, -0.5]);
+		eq(points[1], [0.5, 1.0]);
+
+		let obj_c = ConvexObject::new([3.0, 0.0], verts.clone());
+		assert_eq!(get_contact_manifold(&obj_a, &obj_c), None);
+	}
+
+	#[test]
+	fn test_contact_manifold_corner() {
+		let verts = vec![[-1.0, 1.0], [1.0, 1.0], [1.0, -1.0], [-1.0, -1.0]];
+		let mut obj_a = ConvexObject::new([0.0, 0.0], verts.clone());
+		obj_a.set_rotation(f32::consts::FRAC_PI_4);
+		let obj_b = ConvexObject::new([0.0, 2.2], verts.clone());
+		let manifold = get_contact_manifold(&obj_a, &obj_b).unwrap();
+		eq_eps(manifold.normal, [0.0, -1.0], 0.00001);
+		assert!((manifold.depth - (f32::consts::SQRT_2 - 1.2)).abs() < 0.00001);
+		assert_eq!(manifold.get_points().len(), 1);
+		eq_eps(manifold.get_points()[0], [0.0, f32::consts::SQRT_2], 0.00001);
+
+		// Same contact seen from the other shape
+		let manifold = get_contact_manifold(&obj_b, &obj_a).unwrap();
+		eq_eps(manifold.normal, [0.0, 1.0], 0.00001);
+		assert_eq!(manifold.get_points().len(), 1);
+		eq_eps(manifold.get_points()[0], [0.0, f32::consts::SQRT_2], 0.00001);
+	}
+
+	#[test]
+	fn test_contact_manifold_circle() {
+		let verts = vec![[-1.0, 1.0], [1.0, 1.0], [1.0, -1.0], [-1.0, -1.0]];
+		let obj_a = ConvexObject::new([0.0, 0.0], verts);
+		let obj_b = CircleObject::new([2.0, 0.0], 1.5);
+		let manifold = get_contact_manifold(&obj_a, &obj_b).unwrap();
+		eq(manifold.normal, [-1.0, 0.0]);
+		assert!((manifold.depth - 0.5).abs() < 0.000001);
+		assert_eq!(manifold.get_points(), &[[0.5, 0.0]]);
+
+		let manifold = get_contact_manifold(&obj_b, &obj_a).unwrap();
+		eq(manifold.normal, [1.0, 0.0]);
+		assert_eq!(manifold.get_points(), &[[0.5, 0.0]]);
+
+		let obj_a = CircleObject::new([0.0, 0.0], 3.0);
+		let obj_b = CircleObject::new([3.0, 3.0], 2.0);
+		let manifold = get_contact_manifold(&obj_a, &obj_b).unwrap();
+		assert!((manifold.depth - 0.7573593).abs() < 0.00001);
+		eq_eps(manifold.get_points()[0], [2.1213203, 2.1213203], 0.00001);
+	}
+
+	#[test]
+	fn test_raycast_convex() {
+		let verts = vec![[-1.0, 1.0], [1.0, 1.0], [1.0, -1.0], [-1.0, -1.0]];
+		let mut obj = ConvexObject::new([0.0, 0.0], verts);
+		let hit = raycast(&obj, [-5.0, 0.0], [1.0, 0.0], 10.0).unwrap();
+		assert_eq!(hit, RayHit { distance: 4.0, point: [-1.0, 0.0], normal: [-1.0, 0.0] });
+
+		let hit = raycast(&obj, [0.0, 5.0], [0.0, -1.0], 10.0).unwrap();
+		assert_eq!(hit, RayHit { distance: 4.0, point: [0.0, 1.0], normal: [0.0, 1.0] });
+
+		assert_eq!(raycast(&obj, [-5.0, 2.0], [1.0, 0.0], 10.0), None);
+		assert_eq!(raycast(&obj, [-5.0, 0.0], [-1.0, 0.0], 10.0), None);
+		assert_eq!(raycast(&obj, [-5.0, 0.0], [1.0, 0.0], 3.0), None);
+		// Starts inside
+		assert_eq!(raycast(&obj, [0.5, 0.0], [1.0, 0.0], 10.0), None);
+
+		obj.set_rotation(f32::consts::FRAC_PI_4);
+		let hit = raycast(&obj, [-5.0, 0.0], [1.0, 0.0], 10.0).unwrap();
+		assert!((hit.distance - (5.0 - f32::consts::SQRT_2)).abs() < 0.00001);
+	}
+
+	#[test]
+	fn test_raycast_circle() {
+		let mut obj = CircleObject::new([5.0, 0.0], 2.0);
+		let hit = raycast(&obj, [0.0, 0.0], [1.0, 0.0], 10.0).unwrap();
+		assert_eq!(hit, RayHit { distance: 3.0, point: [3.0, 0.0], normal: [-1.0, 0.0] });
+		assert_eq!(raycast(&obj, [0.0, 0.0], [0.0, 1.0], 10.0), None);
+		assert_eq!(raycast(&obj, [5.0, 1.0], [1.0, 0.0], 10.0), None);
+
+		obj.set_scale([2.0, 2.0]);
+		let hit = raycast(&obj, [0.0, 0.0], [1.0, 0.0], 10.0).unwrap();
+		assert!((hit.distance - 1.0).abs() < 0.00001);
+	}
+
+	#[test]
+	fn test_circle_cast() {
+		let verts = vec![[-1.0, 1.0], [1.0, 1.0], [1.0, -1.0], [-1.0, -1.0]];
+		let obj_a = ConvexObject::new([0.0, 0.0], verts);
+		let hit = circle_cast(&obj_a, [-5.0, 0.0], 0.5, [1.0, 0.0], 10.0).unwrap();
+		assert!((hit.distance - 3.5).abs() < 0.00001);
+		eq(hit.point, [-1.0, 0.0]);
+		eq(hit.normal, [-1.0, 0.0]);
+
+		// Wider than the ray, so it clips the corner
+		assert!(circle_cast(&obj_a, [-5.0, 1.3], 0.5, [1.0, 0.0], 10.0).is_some());
+		assert_eq!(raycast(&obj_a, [-5.0, 1.3], [1.0, 0.0], 10.0), None);
+
+		let obj_b = CircleObject::new([5.0, 0.0], 1.0);
+		let hit = circle_cast(&obj_b, [0.0, 0.0], 1.0, [1.0, 0.0], 10.0).unwrap();
+		assert!((hit.distance - 3.0).abs() < 0.00001);
+		eq(hit.point, [4.0, 0.0]);
+	}
+
+	#[test]
+	fn test_time_of_impact_convex() {
+		let verts = vec![[-1.0, 1.0], [1.0, 1.0], [1.0, -1.0], [-1.0, -1.0]];
+		let obj_a = ConvexObject::new([0.0, 0.0], verts);
+		let obj_b = CircleObject::new([-5.0, 0.0], 0.5);
+		let toi = get_time_of_impact(&obj_b, [10.0, 0.0], &obj_a, [0.0, 0.0]).unwrap();
+		assert!((toi.time - 0.35).abs() < 0.00001);
+		eq(toi.normal, [-1.0, 0.0]);
+
+		// Far faster than the box is thick
+		let toi = get_time_of_impact(&obj_b, [1000.0, 0.0], &obj_a, [0.0, 0.0]).unwrap();
+		assert!((toi.time - 0.0035).abs() < 0.00001);
+
+		// The box moving into the circle is the same as the other way around
+		let toi = get_time_of_impact(&obj_b, [0.0, 0.0], &obj_a, [-10.0, 0.0]).unwrap();
+		assert!((toi.time - 0.35).abs() < 0.00001);
+
+		assert_eq!(get_time_of_impact(&obj_b, [-10.0, 0.0], &obj_a, [0.0, 0.0]), None);
+		assert_eq!(get_time_of_impact(&obj_b, [2.0, 0.0], &obj_a, [0.0, 0.0]), None);
+		assert_eq!(get_time_of_impact(&obj_b, [0.0, 10.0], &obj_a, [0.0, 0.0]), None);
+	}
+
+	#[test]
+	fn test_time_of_impact_corner() {
+		let verts = vec![[-1.0, 1.0], [1.0, 1.0], [1.0, -1.0], [-1.0, -1.0]];
+		let obj_a = ConvexObject::new([0.0, 0.0], verts);
+		let obj_b = CircleObject::new([-3.0, 3.0], 0.5);
+		let toi = get_time_of_impact(&obj_b, [4.0, -4.0], &obj_a, [0.0, 0.0]).unwrap();
+		assert!((toi.time - 0.41161165).abs() < 0.00001);
+		eq_eps(toi.normal, [-f32::consts::FRAC_1_SQRT_2, f32::consts::FRAC_1_SQRT_2], 0.00001);
+
+		// Passes just outside the corner
+		let obj_b = CircleObject::new([-3.0, 1.6], 0.5);
+		assert_eq!(get_time_of_impact(&obj_b, [6.0, 0.0], &obj_a, [0.0, 0.0]), None);
+	}
+
+	#[test]
+	fn test_time_of_impact_overlapping() {
+		let verts = vec![[-1.0, 1.0], [1.0, 1.0], [1.0, -1.0], [-1.0, -1.0]];
+		let obj_a = ConvexObject::new([0.0, 0.0], verts);
+		let obj_b = CircleObject::new([-1.2, 0.0], 0.5);
+		assert_eq!(get_time_of_impact(&obj_b, [10.0, 0.0], &obj_a, [0.0, 0.0]), None);
+
+		let obj_c = CircleObject::new([-0.5, 0.0], 1.0);
+		assert_eq!(get_time_of_impact_circles(&obj_b, [10.0, 0.0], &obj_c, [0.0, 0.0]), None);
+	}
+
+	#[test]
+	fn test_time_of_impact_circles() {
+		let obj_a = CircleObject::new([0.0, 0.0], 1.0);
+		let mut obj_b = CircleObject::new([5.0, 0.0], 1.0);
+		let toi = get_time_of_impact_circles(&obj_a, [10.0, 0.0], &obj_b, [0.0, 0.0]).unwrap();
+		assert!((toi.time - 0.3).abs() < 0.00001);
+		eq(toi.normal, [-1.0, 0.0]);
+
+		let toi = get_time_of_impact_circles(&obj_a, [10.0, 0.0], &obj_b, [-10.0, 0.0]).unwrap();
+		assert!((toi.time - 0.15).abs() < 0.00001);
+
+		// Through the generic version, with a scaled circle
+		obj_b.set_scale([2.0, 2.0]);
+		let toi = get_time_of_impact(&obj_a, [10.0, 0.0], &obj_b, [0.0, 0.0]).unwrap();
+		assert!((toi.time - 0.2).abs() < 0.00001);
+
+		assert_eq!(get_time_of_impact_circles(&obj_a, [0.0, 10.0], &obj_b, [0.0, 0.0]), None);
+		assert_eq!(get_time_of_impact_circles(&obj_a, [1.0, 0.0], &obj_b, [0.0, 0.0]), None);
+	}
+
+	#[test]
+	fn test_aabb() {
+		let verts = vec![[-2.0, 1.0], [2.0, 1.0], [2.0, -1.0], [-2.0, -1.0]];
+		let mut obj_a = ConvexObject::new([1.0, 0.0], verts);
+		assert_eq!(obj_a.get_aabb(), Aabb::new([-1.0, -1.0], [3.0, 1.0]));
+		obj_a.set_rotation(f32::consts::FRAC_PI_2);
+		let aabb = obj_a.get_aabb();
+		eq_eps(aabb.min, [0.0, -2.0], 0.00001);
+		eq_eps(aabb.max, [2.0, 2.0], 0.00001);
+
+		let mut obj_b = CircleObject::new([4.0, 0.0], 1.0);
+		obj_b.set_scale([2.0, 2.0]);
+		assert_eq!(obj_b.get_aabb(), Aabb::new([2.0, -2.0], [6.0, 2.0]));
+		assert!(obj_b.get_aabb().overlaps(&aabb));
+		assert!(!obj_b.get_aabb().overlaps(&Aabb::new([-5.0, -5.0], [1.9, 5.0])));
+		assert!(obj_b.get_aabb().overlaps(&Aabb::new([-5.0, -5.0], [1.9, 5.0]).expanded(0.1)));
+	}
+
+	const ALL_SHAPES: [ShapeType; 7] = [
+		ShapeType::Convex, ShapeType::Circle, ShapeType::Capsule,
+		ShapeType::Segment, ShapeType::Aabb, ShapeType::Chain, ShapeType::Compound,
+	];
+
+	// About two units across
+	fn make_shape(shape_type: ShapeType, position: Vector2<f32>) -> Box<dyn Shape> {
+		match shape_type {
+			ShapeType::Convex => Box::new(ConvexObject::new(position, vec![[-1.0, -1.0], [0.0, 1.0], [1.0, -1.0]])),
+			ShapeType::Circle => Box::new(CircleObject::new(position, 1.0)),
+			ShapeType::Capsule => Box::new(CapsuleObject::new(position, 1.0, 0.5)),
+			ShapeType::Segment => Box::new(SegmentObject::new(vec2_add(position, [-1.0, -1.0]), vec2_add(position, [1.0, 1.0]))),
+			ShapeType::Aabb => Box::new(AabbObject::new(position, [1.0, 1.0])),
+			ShapeType::Chain => Box::new(ChainObject::new(position, vec![[-1.5, -1.0], [0.0, 1.0], [1.5, -1.0]], false)),
+			ShapeType::Compound => Box::new(CompoundObject::new(position, &[[-1.0, -1.0], [1.0, -1.0], [1.0, 0.0], [0.0, 0.0], [0.0, 1.0], [-1.0, 1.0]]).unwrap()),
+		}
+	}
+
+	#[test]
+	fn test_collision_matrix() {
+		for a_type in ALL_SHAPES.iter() {
+			for b_type in ALL_SHAPES.iter() {
+				let a = make_shape(*a_type, [0.0, 0.0]);
+				let mut b = make_shape(*b_type, [0.3, -0.4]);
+				// So two segments are not parallel
+				b.set_rotation(f32::consts::FRAC_PI_2);
+
+				let (disp, normal) = get_collision(&*a, &*b).unwrap_or_else(|| panic!("{:?} {:?}", a_type, b_type));
+				assert!(vec2_len(disp) > 0.0 && vec2_len(disp) < 3.0, "{:?} {:?} {:?}", a_type, b_type, disp);
+				eq_eps(normal, vec2_normalized(disp), 0.00001);
+				let (back, _) = get_collision(&*b, &*a).unwrap();
+				eq_eps(back, vec2_neg(disp), 0.00001);
+
+				b.set_position([6.0, 0.0]);
+				assert_eq!(get_collision(&*a, &*b), None, "{:?} {:?}", a_type, b_type);
+				assert!(!a.get_aabb().overlaps(&b.get_aabb()));
+			}
+		}
+	}
+
+	#[test]
+	fn test_distance_matrix() {
+		for a_type in ALL_SHAPES.iter() {
+			for b_type in ALL_SHAPES.iter() {
+				let a = make_shape(*a_type, [0.0, 0.0]);
+				let mut b = make_shape(*b_type, [0.3, -0.4]);
+				b.set_rotation(f32::consts::FRAC_PI_2);
+
+				// Compounds measure to their deepest piece rather than out of the whole shape
+				let result = get_distance(&*a, &*b);
+				let (disp, _) = get_collision(&*a, &*b).unwrap();
+				if *a_type == ShapeType::Compound || *b_type == ShapeType::Compound {
+					assert!(result.distance < 0.0 && -result.distance < vec2_len(disp) + 0.001, "{:?} {:?}", a_type, b_type);
+				} else {
+					assert!((result.distance + vec2_len(disp)).abs() < 0.001, "{:?} {:?} {:?}", a_type, b_type, result);
+				}
+
+				b.set_position([6.0, 0.0]);
+				let result = get_distance(&*a, &*b);
+				assert!(result.distance > 0.0, "{:?} {:?}", a_type, b_type);
+				assert!((vec2_len(result.normal) - 1.0).abs() < 0.00001);
+				let offset = vec2_scale(result.normal, result.distance);
+				eq_eps(result.point_a, vec2_add(result.point_b, offset), 0.0001);
+				let back = get_distance(&*b, &*a);
+				assert!((back.distance - result.distance).abs() < 0.0001, "{:?} {:?}", a_type, b_type);
+				eq_eps(back.normal, vec2_neg(result.normal), 0.0001);
+			}
+		}
+	}
+
+	// The capsule and the segment shrunk down to a circle and a point
+	fn make_degenerate_shapes(position: Vector2<f32>) -> [Box<dyn Shape>; 2] {
+		[Box::new(CapsuleObject::new(position, 0.0, 0.5)), Box::new(SegmentObject::new(position, position))]
+	}
+
+	#[test]
+	fn test_degenerate_matrix() {
+		for b_type in ALL_SHAPES.iter() {
+			let b = make_shape(*b_type, [0.0, 0.0]);
+			for i in 0..7 {
+				for j in 0..7 {
+					let position = [i as f32 * 0.7 - 2.1, j as f32 * 0.7 - 2.1];
+					for a in make_degenerate_shapes(position).iter() {
+						// Too close to touching to tell
+						let distance = get_distance(&**a, &*b).distance;
+						if distance.abs() < 0.001 {
+							continue;
+						}
+						for &narrow_phase in &[NarrowPhase::Sat, NarrowPhase::Gjk] {
+							let info = (a.get_type(), b_type, position, narrow_phase, distance);
+							let collision = get_collision_with(&**a, &*b, narrow_phase);
+							assert_eq!(collision.is_some(), distance < 0.0, "{:?}", info);
+							assert_eq!(get_collision_with(&*b, &**a, narrow_phase).is_some(), distance < 0.0, "{:?}", info);
+							if let Some((disp, _)) = collision {
+								assert!(disp.iter().all(|x| x.is_finite()), "{:?}", info);
+								if *b_type != ShapeType::Compound {
+									assert!((vec2_len(disp) + distance).abs() < 0.001, "{:?} {:?}", info, disp);
+								}
+							}
+						}
+					}
+				}
+			}
+		}
+	}
+
+	#[test]
+	fn test_collision_capsule() {
+		let mut obj_a = CapsuleObject::new([0.0, 0.0], 2.0, 0.5);
+		// Over the flat side
+		let obj_b = CircleObject::new([1.5, 1.2], 1.0);
+		eq_eps(get_collision(&obj_a, &obj_b).unwrap().0, [0.0, -0.3], 0.00001);
+		// Past the rounded end
+		let obj_c = CircleObject::new([3.2, 0.0], 1.0);
+		eq_eps(get_collision(&obj_a, &obj_c).unwrap().0, [-0.3, 0.0], 0.00001);
+		let obj_d = CircleObject::new([3.0, 1.0], 0.5);
+		assert_eq!(get_collision(&obj_a, &obj_d), None);
+
+		let obj_e = CapsuleObject::new([1.0, 0.9], 2.0, 0.5);
+		eq_eps(get_collision(&obj_a, &obj_e).unwrap().0, [0.0, -0.1], 0.00001);
+
+		// Zero length is a circle
+		let obj_f = CapsuleObject::new([0.0, 0.0], 0.0, 1.0);
+		let obj_g = CircleObject::new([1.5, 0.0], 1.0);
+		eq_eps(get_collision(&obj_f, &obj_g).unwrap().0, [-0.5, 0.0], 0.00001);
+		eq_eps(get_collision_with(&obj_f, &obj_g, NarrowPhase::Gjk).unwrap().0, [-0.5, 0.0], 0.00001);
+		assert!((get_distance(&obj_f, &obj_g).distance + 0.5).abs() < 0.00001);
+		assert_eq!(get_contact_manifold(&obj_f, &obj_g).unwrap().get_points().len(), 1);
+
+		obj_a.set_rotation(f32::consts::FRAC_PI_2);
+		let verts = world_verts(&obj_a);
+		eq_eps(verts[0], [0.0, -2.0], 0.00001);
+		eq_eps(verts[1], [0.0, 2.0], 0.00001);
+		assert_eq!(get_collision(&obj_a, &obj_c), None);
+
+		obj_a.set_scale([1.0, 2.0]);
+		assert_eq!(obj_a.get_radius(), 1.0);
+		let aabb = obj_a.get_aabb();
+		eq_eps(aabb.min, [-1.0, -3.0], 0.00001);
+		eq_eps(aabb.max, [1.0, 3.0], 0.00001);
+	}
+
+	#[test]
+	fn test_collision_segment() {
+		let obj_a = SegmentObject::new([-2.0, 0.0], [2.0, 0.0]);
+		eq(obj_a.get_position(), [0.0, 0.0]);
+		// Lying inside the circle, pushed out the short way
+		let obj_b = CircleObject::new([0.0, 0.5], 1.0);
+		eq_eps(get_collision(&obj_a, &obj_b).unwrap().0, [0.0, -0.5], 0.00001);
+		eq_eps(get_collision(&obj_b, &obj_a).unwrap().0, [0.0, 0.5], 0.00001);
+
+		// Round the end
+		let obj_c = CircleObject::new([2.6, 0.8], 1.1);
+		eq_eps(get_collision(&obj_c, &obj_a).unwrap().0, [0.06, 0.08], 0.00001);
+
+		let verts = vec![[-1.0, 1.0], [1.0, 1.0], [1.0, -1.0], [-1.0, -1.0]];
+		let obj_d = ConvexObject::new([0.0, 0.8], verts);
+		eq_eps(get_collision(&obj_a, &obj_d).unwrap().0, [0.0, -0.2], 0.00001);
+
+		// On the same line
+		let obj_e = CapsuleObject::new([3.2, 0.0], 1.0, 0.3);
+		eq_eps(get_collision(&obj_a, &obj_e).unwrap().0, [-0.1, 0.0], 0.00001);
+		// Without width, segments on one line only ever touch
+		let obj_f = SegmentObject::new([1.5, 0.0], [4.0, 0.0]);
+		assert_eq!(get_collision(&obj_a, &obj_f), None);
+
+		// Zero length is a point
+		let obj_g = SegmentObject::new([0.5, 0.5], [0.5, 0.5]);
+		eq_eps(get_collision(&obj_g, &obj_b).unwrap().0, [0.5, 0.0], 0.00001);
+		eq_eps(get_collision_with(&obj_g, &obj_b, NarrowPhase::Gjk).unwrap().0, [0.5, 0.0], 0.00001);
+		let manifold = get_contact_manifold(&obj_d, &obj_g).unwrap();
+		assert_eq!(manifold.get_points(), &[[0.5, 0.5]]);
+		let hit = raycast(&obj_g, [-5.0, 0.5], [1.0, 0.0], 10.0).unwrap();
+		assert!((hit.distance - 5.5).abs() < 0.00001);
+		eq(hit.normal, [-1.0, 0.0]);
+	}
+
+	#[test]
+	fn test_collision_aabb() {
+		let mut obj_a = AabbObject::new([0.0, 0.0], [2.0, 1.0]);
+		let obj_b = ConvexObject::new([0.0, 0.0], vec![[-2.0, 1.0], [2.0, 1.0], [2.0, -1.0], [-2.0, -1.0]]);
+		let obj_c = CircleObject::new([2.5, 1.2], 1.0);
+		// Same as the box built from verts
+		eq_eps(get_collision(&obj_a, &obj_c).unwrap().0, get_collision(&obj_b, &obj_c).unwrap().0, 0.00001);
+		assert_eq!(obj_a.get_aabb(), obj_b.get_aabb());
+
+		obj_a.set_rotation(1.0);
+		assert_eq!(obj_a.get_rotation(), 0.0);
+		obj_a.set_scale([2.0, 1.0]);
+		let obj_d = CircleObject::new([4.5, 0.0], 1.0);
+		eq_eps(get_collision(&obj_a, &obj_d).unwrap().0, [-0.5, 0.0], 0.00001);
+		assert_eq!(obj_a.get_aabb(), Aabb::new([-4.0, -1.0], [4.0, 1.0]));
+	}
+
+	#[test]
+	fn test_collision_chain() {
+		let verts = vec![[-2.0, -2.0], [2.0, -2.0], [2.0, 2.0], [-2.0, 2.0]];
+		let mut obj_a = ChainObject::new([0.0, 0.0], verts, true);
+		assert_eq!(world_verts(&obj_a).len(), 5);
+		assert_eq!(obj_a.get_aabb(), Aabb::new([-2.0, -2.0], [2.0, 2.0]));
+
+		// Hollow, only the walls collide
+		let obj_b = CircleObject::new([0.0, 0.0], 1.0);
+		assert_eq!(get_collision(&obj_b, &obj_a), None);
+		let obj_c = CircleObject::new([1.5, 0.0], 1.0);
+		eq_eps(get_collision(&obj_c, &obj_a).unwrap().0, [-0.5, 0.0], 0.00001);
+		// Deepest of the two walls in a corner
+		let obj_d = CircleObject::new([1.5, 1.8], 1.0);
+		eq_eps(get_collision(&obj_d, &obj_a).unwrap().0, [0.0, -0.8], 0.00001);
+
+		let obj_e = CircleObject::new([-2.0, 0.0], 1.0);
+		assert!(get_collision(&obj_e, &obj_a).is_some());
+		obj_a.set_closed(false);
+		assert_eq!(get_collision(&obj_e, &obj_a), None);
+
+		// The segments follow the chain
+		obj_a.set_position([1.0, 0.0]);
+		assert_eq!(obj_a.get_aabb(), Aabb::new([-1.0, -2.0], [3.0, 2.0]));
+		assert_eq!(get_collision(&obj_c, &obj_a), None);
+	}
+
+	#[test]
+	fn test_contact_manifold_shapes() {
+		// A capsule lying on a box touches at both ends
+		let obj_a = CapsuleObject::new([0.0, 0.0], 2.0, 0.5);
+		let obj_b = AabbObject::new([0.0, -1.25], [3.0, 1.0]);
+		let manifold = get_contact_manifold(&obj_a, &obj_b).unwrap();
+		eq_eps(manifold.normal, [0.0, 1.0], 0.00001);
+		assert!((manifold.depth - 0.25).abs() < 0.00001);
+		assert_eq!(manifold.get_points(), &[[-2.0, -0.5], [2.0, -0.5]]);
+
+		let obj_c = SegmentObject::new([-1.0, -0.5], [1.0, -0.5]);
+		let manifold = get_contact_manifold(&obj_c, &obj_b).unwrap();
+		assert_eq!(manifold.get_points().len(), 2);
+
+		let verts = vec![[-2.0, -2.0], [2.0, -2.0], [2.0, 2.0]];
+		let obj_d = ChainObject::new([0.0, 0.0], verts, false);
+		let obj_e = CircleObject::new([1.5, 1.8], 1.0);
+		let manifold = get_contact_manifold(&obj_e, &obj_d).unwrap();
+		eq_eps(manifold.normal, [-1.0, 0.0], 0.00001);
+		eq_eps(manifold.get_points()[0], [2.5, 1.8], 0.00001);
+	}
+
+	#[test]
+	fn test_raycast_shapes() {
+		let obj_a = CapsuleObject::new([0.0, 0.0], 2.0, 0.5);
+		let hit = raycast(&obj_a, [1.0, 5.0], [0.0, -1.0], 10.0).unwrap();
+		assert!((hit.distance - 4.5).abs() < 0.00001);
+		eq_eps(hit.normal, [0.0, 1.0], 0.00001);
+		let hit = raycast(&obj_a, [-5.0, 0.0], [1.0, 0.0], 10.0).unwrap();
+		assert!((hit.distance - 2.5).abs() < 0.00001);
+		eq_eps(hit.normal, [-1.0, 0.0], 0.00001);
+		assert_eq!(raycast(&obj_a, [0.0, 0.2], [0.0, 1.0], 10.0), None);
+
+		// Both sides of a segment
+		let obj_b = SegmentObject::new([-2.0, 0.0], [2.0, 0.0]);
+		let hit = raycast(&obj_b, [0.0, -5.0], [0.0, 1.0], 10.0).unwrap();
+		assert!((hit.distance - 5.0).abs() < 0.00001);
+		eq_eps(hit.normal, [0.0, -1.0], 0.00001);
+		assert_eq!(raycast(&obj_b, [3.0, -5.0], [0.0, 1.0], 10.0), None);
+
+		let obj_c = AabbObject::new([0.0, 0.0], [1.0, 1.0]);
+		let hit = raycast(&obj_c, [-5.0, 0.0], [1.0, 0.0], 10.0).unwrap();
+		assert_eq!(hit, RayHit { distance: 4.0, point: [-1.0, 0.0], normal: [-1.0, 0.0] });
+
+		// From inside a closed chain
+		let verts = vec![[-2.0, -2.0], [2.0, -2.0], [2.0, 2.0], [-2.0, 2.0]];
+		let obj_d = ChainObject::new([0.0, 0.0], verts, true);
+		let hit = raycast(&obj_d, [0.0, 0.0], [1.0, 0.0], 10.0).unwrap();
+		assert!((hit.distance - 2.0).abs() < 0.00001);
+		eq_eps(hit.normal, [-1.0, 0.0], 0.00001);
+	}
+
+	#[test]
+	fn test_time_of_impact_shapes() {
+		let obj_a = CircleObject::new([-5.0, 0.0], 0.5);
+
+		let obj_b = CapsuleObject::new([0.0, 0.0], 2.0, 0.5);
+		let toi = get_time_of_impact(&obj_a, [10.0, 0.0], &obj_b, [0.0, 0.0]).unwrap();
+		assert!((toi.time - 0.2).abs() < 0.00001);
+		eq_eps(toi.normal, [-1.0, 0.0], 0.00001);
+
+		let obj_c = SegmentObject::new([0.0, -1.0], [0.0, 1.0]);
+		let toi = get_time_of_impact(&obj_a, [10.0, 0.0], &obj_c, [0.0, 0.0]).unwrap();
+		assert!((toi.time - 0.45).abs() < 0.00001);
+		eq_eps(toi.normal, [-1.0, 0.0], 0.00001);
+		assert_eq!(get_time_of_impact(&obj_a, [10.0, 5.0], &obj_c, [0.0, 0.0]), None);
+
+		let obj_d = AabbObject::new([0.0, 0.0], [1.0, 1.0]);
+		let toi = get_time_of_impact(&obj_a, [10.0, 0.0], &obj_d, [0.0, 0.0]).unwrap();
+		assert!((toi.time - 0.35).abs() < 0.00001);
+
+		// The first wall of the chain in the way
+		let verts = vec![[-2.0, -2.0], [-2.0, 2.0], [2.0, 2.0], [2.0, -2.0]];
+		let obj_e = ChainObject::new([0.0, 0.0], verts, false);
+		let toi = get_time_of_impact(&obj_a, [10.0, 0.0], &obj_e, [0.0, 0.0]).unwrap();
+		assert!((toi.time - 0.25).abs() < 0.00001);
+		eq_eps(toi.normal, [-1.0, 0.0], 0.00001);
+	}
+
+	fn l_shape(position: Vector2<f32>) -> CompoundObject {
+		CompoundObject::new(position, &[[0.0, 0.0], [4.0, 0.0], [4.0, 1.0], [1.0, 1.0], [1.0, 4.0], [0.0, 4.0]]).unwrap()
+	}
+
+	#[test]
+	fn test_collision_compound() {
+		let obj_a = l_shape([0.0, 0.0]);
+		assert_eq!(obj_a.get_piece_count(), 2);
+		assert_eq!(obj_a.get_aabb(), Aabb::new([0.0, 0.0], [4.0, 4.0]));
+
+		let obj_b = CircleObject::new([3.0, 1.2], 0.5);
+		eq_eps(get_collision(&obj_b, &obj_a).unwrap().0, [0.0, 0.3], 0.00001);
+		eq_eps(get_collision(&obj_a, &obj_b).unwrap().0, [0.0, -0.3], 0.00001);
+		// Out through the outline, not through where the pieces meet
+		let obj_c = CircleObject::new([0.5, 0.2], 0.3);
+		eq_eps(get_collision(&obj_c, &obj_a).unwrap().0, [0.0, -0.5], 0.00001);
+		// In the inside corner, out of both arms at once
+		let obj_d = CircleObject::new([1.2, 1.2], 0.5);
+		eq_eps(get_collision(&obj_d, &obj_a).unwrap().0, [0.3, 0.3], 0.00001);
+		// In the gap between the arms
+		let obj_e = CircleObject::new([2.5, 2.5], 1.0);
+		assert_eq!(get_collision(&obj_e, &obj_a), None);
+		assert!(obj_e.get_aabb().overlaps(&obj_a.get_aabb()));
+
+		let manifold = get_contact_manifold(&obj_b, &obj_a).unwrap();
+		eq_eps(manifold.normal, [0.0, 1.0], 0.00001);
+		eq_eps(manifold.get_points()[0], [3.0, 0.7], 0.00001);
+
+		// The pieces follow the shape
+		let mut obj_g = l_shape([10.0, 0.0]);
+		assert_eq!(get_collision(&obj_b, &obj_g), None);
+		obj_g.set_position([0.0, 0.0]);
+		eq_eps(get_collision(&obj_b, &obj_g).unwrap().0, [0.0, 0.3], 0.00001);
+
+		let obj_f = CompoundObject::new([0.0, 0.0], &[[0.0, 0.0], [2.0, 2.0], [2.0, 0.0], [0.0, 2.0]]);
+		assert_eq!(obj_f.err(), Some(PolygonError::SelfIntersecting { first: 0, second: 2 }));
+	}
+
+	#[test]
+	fn test_raycast_compound() {
+		let obj_a = l_shape([0.0, 0.0]);
+		let hit = raycast(&obj_a, [3.0, 3.0], [0.0, -1.0], 10.0).unwrap();
+		assert!((hit.distance - 2.0).abs() < 0.00001);
+		eq(hit.normal, [0.0, 1.0]);
+		let hit = raycast(&obj_a, [6.0, 0.5], [-1.0, 0.0], 10.0).unwrap();
+		assert!((hit.distance - 2.0).abs() < 0.00001);
+		// Starting inside, across where the pieces meet
+		assert_eq!(raycast(&obj_a, [0.5, 0.5], [1.0, 0.0], 10.0), None);
+		assert_eq!(raycast(&obj_a, [2.5, 2.5], vec2_normalized([1.0, 1.0]), 10.0), None);
+
+		let obj_b = CircleObject::new([3.0, 3.0], 0.5);
+		let toi = get_time_of_impact(&obj_b, [0.0, -4.0], &obj_a, [0.0, 0.0]).unwrap();
+		assert!((toi.time - 0.375).abs() < 0.00001);
+		eq_eps(toi.normal, [0.0, 1.0], 0.00001);
+	}
+
+	#[test]
+	fn test_try_new() {
+		let clock_wise = vec![[-1.0, 1.0], [1.0, 1.0], [1.0, -1.0], [-1.0, -1.0]];
+		let obj = ConvexObject::try_new([0.0, 0.0], clock_wise.clone()).unwrap();
+		assert_eq!(obj.verts, clock_wise);
+
+		let mut counter_clock_wise = clock_wise.clone();
+		counter_clock_wise.reverse();
+		let obj_a = ConvexObject::try_new([0.0, 0.0], counter_clock_wise).unwrap();
+		assert_eq!(obj_a.verts, clock_wise);
+		let obj_b = CircleObject::new([2.0, 0.0], 1.5);
+		eq(get_collision(&obj_a, &obj_b).unwrap().0, [-0.5, 0.0]);
+
+		let result = ConvexObject::try_new([0.0, 0.0], vec![[0.0, 0.0], [1.0, 0.0]]);
+		assert_eq!(result.err(), Some(PolygonError::TooFewVerts { count: 2 }));
+		let result = ConvexObject::try_new([0.0, 0.0], vec![[0.0, 0.0], [1.0, 0.0], [1.0, 0.0], [0.0, 1.0]]);
+		assert_eq!(result.err(), Some(PolygonError::DuplicateVert { first: 1, second: 2 }));
+		let result = ConvexObject::try_new([0.0, 0.0], vec![[0.0, 0.0], [1.0, 0.0], [0.5, 0.000001]]);
+		assert_eq!(result.err(), Some(PolygonError::ZeroArea));
+		let result = ConvexObject::try_new([0.0, 0.0], vec![[0.0, 0.0], [2.0, 0.0], [1.0, 0.5], [1.0, 2.0]]);
+		assert_eq!(result.err(), Some(PolygonError::NotConvex));
+	}
+
+	fn eq(a: Vector2<f32>, b: Vector2<f32>) {
+		assert!((a[0] - b[0]).abs() < 0.000001);
+		assert!((a[1] - b[1]).abs() < 0.000001);
+	}
+
+	fn eq_eps(a: Vector2<f32>, b: Vector2<f32>, eps: f32) {
+		assert!((a[0] - b[0]).abs() < eps, "{:?} != {:?}", a, b);
+		assert!((a[1] - b[1]).abs() < eps, "{:?} != {:?}", a, b);
+	}
+
+	fn world_verts(shape: &dyn Shape) -> Vec<Vector2<f32>> {
+		let mut result = Vec::new();
+		shape.visit_verts(&mut |verts| result.extend_from_slice(verts));
+		result
 	}
 }
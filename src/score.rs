@@ -0,0 +1,165 @@
+use events::{CollisionEvent, ContactPhase, Participant};
+use game::CollidableKind;
+use utils::min;
+
+// Every this many consecutive brick hits the multiplier grows by one
+pub const COMBO_STEP: u32 = 3;
+pub const MAX_MULTIPLIER: u32 = 5;
+pub const DEFAULT_PAR_TIME: f64 = 120.0;
+pub const BONUS_PER_SECOND: u32 = 10;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Score {
+	pub points: u32,
+	// Brick hits since a ball last touched the paddle
+	pub combo: u32,
+	// Clearing the level faster than this earns a bonus
+	pub par_time: f64,
+	pub level_started_at: Option<f64>,
+}
+
+impl Default for Score {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Score {
+	pub fn new() -> Self {
+		Score {
+			points: 0,
+			combo: 0,
+			par_time: DEFAULT_PAR_TIME,
+			level_started_at: None,
+		}
+	}
+
+	pub fn reset(&mut self) {
+		*self = Score::new();
+	}
+
+	pub fn get_multiplier(&self) -> u32 {
+		min(1 + self.combo / COMBO_STEP, MAX_MULTIPLIER)
+	}
+
+	// Points are only awarded when the brick is destroyed, every hit grows the combo
+	pub fn on_brick_hit(&mut self, destroyed_for_points: Option<u32>) {
+		if let Some(points) = destroyed_for_points {
+			self.points += points * self.get_multiplier();
+		}
+		self.combo += 1;
+	}
+
+	pub fn on_paddle_hit(&mut self) {
+		self.combo = 0;
+	}
+
+	// Subscribed to WebGame::collision_events. Every step a ball touches the paddle or a brick is a hit
+	pub fn on_collision_event(&mut self, event: &CollisionEvent) {
+		let contact = &event.contact;
+		if event.phase == ContactPhase::End || contact.a_type != Participant::Ball {
+			return;
+		}
+		match contact.b_type {
+			Participant::Object(CollidableKind::Player) => self.on_paddle_hit(),
+			Participant::Object(CollidableKind::Brick) => self.on_brick_hit(contact.destroyed_for_points),
+			_ => {}
+		}
+	}
+
+	pub fn on_level_start(&mut self, par_time: f64) {
+		self.combo = 0;
+		self.par_time = par_time;
+		self.level_started_at = None;
+	}
+
+	// The clock starts when the first ball is launched
+	pub fn on_launch(&mut self, time: f64) {
+		if self.level_started_at.is_none() {
+			self.level_started_at = Some(time);
+		}
+	}
+
+	// Returns the time bonus
+	pub fn on_level_complete(&mut self, time: f64) -> u32 {
+		let bonus = match self.level_started_at {
+			Some(started_at) => {
+				let seconds_left = self.par_time - (time - started_at);
+				if seconds_left > 0.0 { seconds_left as u32 * BONUS_PER_SECOND } else { 0 }
+			}
+			None => 0,
+		};
+		// The combo is left to on_level_start, the events of the last hits may not be dispatched yet
+		self.points += bonus;
+		bonus
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use events::Contact;
+
+	#[test]
+	fn test_combo() {
+		let mut score = Score::new();
+		score.on_brick_hit(Some(10));
+		score.on_brick_hit(None);
+		score.on_brick_hit(None);
+		assert_eq!(score.points, 10);
+		assert_eq!(score.get_multiplier(), 2);
+
+		score.on_brick_hit(Some(10));
+		assert_eq!(score.points, 30);
+
+		score.on_paddle_hit();
+		assert_eq!(score.get_multiplier(), 1);
+		score.on_brick_hit(Some(10));
+		assert_eq!(score.points, 40);
+	}
+
+	#[test]
+	fn test_collision_events() {
+		let mut score = Score::new();
+		let hit = |phase: ContactPhase, a_type: Participant, b_type: Participant, destroyed_for_points: Option<u32>| CollisionEvent {
+			phase,
+			contact: Contact { a: 1, a_type, b: 2, b_type, point: [0.0, 0.0], normal: [0.0, 1.0], relative_velocity: [0.0, 0.0], destroyed_for_points },
+		};
+		let brick = Participant::Object(CollidableKind::Brick);
+		score.on_collision_event(&hit(ContactPhase::Begin, Participant::Ball, brick, None));
+		score.on_collision_event(&hit(ContactPhase::Stay, Participant::Ball, brick, Some(10)));
+		assert_eq!((score.points, score.combo), (10, 2));
+
+		// Contacts ending and the paddle moving into a brick are not hits
+		score.on_collision_event(&hit(ContactPhase::End, Participant::Ball, brick, Some(10)));
+		score.on_collision_event(&hit(ContactPhase::Begin, Participant::Object(CollidableKind::Player), brick, Some(10)));
+		assert_eq!((score.points, score.combo), (10, 2));
+
+		score.on_collision_event(&hit(ContactPhase::Begin, Participant::Ball, Participant::Object(CollidableKind::Player), None));
+		assert_eq!(score.combo, 0);
+	}
+
+	#[test]
+	fn test_multiplier_cap() {
+		let mut score = Score::new();
+		for _ in 0..100 {
+			score.on_brick_hit(None);
+		}
+		assert_eq!(score.get_multiplier(), MAX_MULTIPLIER);
+	}
+
+	#[test]
+	fn test_time_bonus() {
+		let mut score = Score::new();
+		score.on_level_start(60.0);
+		score.on_launch(100.0);
+		// Only the first launch starts the clock
+		score.on_launch(110.0);
+		assert_eq!(score.on_level_complete(130.5), 29 * BONUS_PER_SECOND);
+		assert_eq!(score.points, 29 * BONUS_PER_SECOND);
+
+		score.on_level_start(60.0);
+		score.on_launch(200.0);
+		assert_eq!(score.on_level_complete(300.0), 0);
+	}
+}
@@ -0,0 +1,988 @@
+use std::mem;
+use std::ops::AddAssign;
+
+use game::*;
+use level::{BallDesc, Level, LEVELS, DEFAULT_BALL_MASS, DEFAULT_BALL_RESTITUTION, load_level, spawn_ball};
+use events::{Contact, Participant};
+use sat::{get_collision_with, get_distance};
+use vecmath::vec2_sub;
+use utils::split_vec_mut_around;
+
+pub const STARTING_LIVES: u32 = 3;
+const SERVE_VELOCITY: [f32; 2] = [85.0, 85.0];
+const SERVE_BALL_RADIUS: f32 = 2.5;
+pub const DEFAULT_TICK_RATE: f64 = 120.0;
+// Time beyond this many steps per frame is dropped so a slow frame can not snowball
+pub const DEFAULT_MAX_STEPS: u32 = 8;
+
+pub fn initial_render_call(state: &mut WebGame) {
+	load_level_index(state, 0);
+	state.lives = STARTING_LIVES;
+	state.game_state = GameState::Attract;
+}
+
+// Built in levels wrap around after the last one
+pub fn load_level_index(state: &mut WebGame, index: usize) {
+	let index = index % LEVELS.len();
+	let level = Level::parse(LEVELS[index]).expect("built in levels are valid");
+	load_level(state, &level);
+	state.level_index = index;
+}
+
+// Collisions resolved during a single update
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct FrameStats {
+	pub object_collisions: u32,
+	// Counted once per colliding pair
+	pub ball_collisions: u32,
+	pub balls_lost: u32,
+}
+
+impl AddAssign for FrameStats {
+	fn add_assign(&mut self, other: FrameStats) {
+		self.object_collisions += other.object_collisions;
+		self.ball_collisions += other.ball_collisions;
+		self.balls_lost += other.balls_lost;
+	}
+}
+
+// Runs update in steps of a fixed size however long the rendered frames are
+#[derive(Debug, Clone, PartialEq)]
+pub struct FixedTimestep {
+	step: f64,
+	max_steps: u32,
+	accumulator: f64,
+}
+
+impl Default for FixedTimestep {
+	fn default() -> Self {
+		FixedTimestep::new(DEFAULT_TICK_RATE, DEFAULT_MAX_STEPS)
+	}
+}
+
+impl FixedTimestep {
+	pub fn new(tick_rate: f64, max_steps: u32) -> Self {
+		assert!(tick_rate > 0.0 && max_steps > 0);
+		FixedTimestep {
+			step: 1.0 / tick_rate,
+			max_steps,
+			accumulator: 0.0,
+		}
+	}
+
+	pub fn get_step(&self) -> f64 {
+		self.step
+	}
+
+	// Fraction of a step left over after the last advance, for WebGame::render
+	pub fn get_alpha(&self) -> f64 {
+		self.accumulator / self.step
+	}
+
+	// Returns the stats of all steps taken
+	pub fn advance(&mut self, state: &mut WebGame, dt: f64) -> FrameStats {
+		let mut stats = FrameStats::default();
+		self.accumulator += dt;
+		// The mouse only moves once per frame. The paddle follows it evenly over the steps of
+		// the frame, so its speed does not depend on the step or on the frame rate
+		let steps_due = self.steps_due();
+		let mouse_x = state.mouse_x;
+		let from = state.last_mouse_x.unwrap_or(mouse_x);
+		let mut steps = 0;
+		while self.accumulator >= self.step {
+			if steps == self.max_steps {
+				self.accumulator %= self.step;
+				break;
+			}
+			state.mouse_x = from + (mouse_x - from) * (steps + 1) as f32 / steps_due as f32;
+			state.save_previous_state();
+			stats += update(state, self.step);
+			self.accumulator -= self.step;
+			steps += 1;
+		}
+		state.mouse_x = mouse_x;
+		stats
+	}
+
+	// Steps the loop in advance takes for the time accumulated
+	fn steps_due(&self) -> u32 {
+		let mut accumulator = self.accumulator;
+		let mut steps = 0;
+		while accumulator >= self.step && steps < self.max_steps {
+			accumulator -= self.step;
+			steps += 1;
+		}
+		steps
+	}
+}
+
+pub fn update(state: &mut WebGame, dt: f64) -> FrameStats {
+	let mut stats = FrameStats::default();
+	let pressed = mem::take(&mut state.pressed_buttons);
+	let space = pressed.contains(&ButtonState::Space);
+	let esc = pressed.contains(&ButtonState::Esc);
+	// The mouse moves freely while the paddle does not follow it, that is no paddle speed
+	if state.game_state != GameState::Serve && state.game_state != GameState::Playing {
+		state.last_mouse_x = None;
+	}
+
+	match state.game_state {
+		GameState::Attract | GameState::GameOver => {
+			if space {
+				state.score.borrow_mut().reset();
+				load_level_index(state, 0);
+				state.lives = STARTING_LIVES;
+				state.game_state = GameState::Serve;
+			}
+			return stats;
+		}
+		GameState::LevelComplete => {
+			if space {
+				let next = state.level_index + 1;
+				load_level_index(state, next);
+				state.game_state = GameState::Serve;
+			}
+			return stats;
+		}
+		GameState::Paused => {
+			if esc {
+				state.game_state = state.resume_state;
+			}
+			return stats;
+		}
+		GameState::Serve | GameState::Playing if esc => {
+			state.resume_state = state.game_state;
+			state.game_state = GameState::Paused;
+			return stats;
+		}
+		GameState::Serve => {
+			// Balls ride along with the paddle
+			let offset = move_player(state, dt);
+			for ball in &mut state.balls {
+				let position = ball.get_position();
+				ball.set_position([position[0] + offset, position[1]]);
+			}
+			if space {
+				state.score.borrow_mut().on_launch(state.current_time_in_seconds);
+				state.game_state = GameState::Playing;
+			}
+			return stats;
+		}
+		GameState::Playing => {}
+	}
+
+	move_player(state, dt);
+	let player = state.player.as_mut().unwrap();
+
+	let mut objects_to_check_collision_against = Vec::new();
+	objects_to_check_collision_against.push(player);
+	for obj in &mut state.objects {
+		obj.as_mut().update();
+		objects_to_check_collision_against.push(obj.as_mut());
+	}
+
+	// The paddle only moves into sensors, solid objects do not push it around
+	if let Some((player, objects)) = objects_to_check_collision_against.split_first_mut() {
+		for object in objects.iter_mut().filter(|object| !object.is_solid() && !object.is_destroyed()) {
+			if !player.filter.collides_with(&object.filter) {
+				continue;
+			}
+			if let Some((disp, normal)) = get_collision_with(player.get_shape(), object.get_shape(), object.narrow_phase) {
+				object.on_collision(&mut state.scene, &disp, &normal);
+				state.collision_events.add_contact(Contact {
+					a: player.object.get_id(),
+					a_type: Participant::Object(CollidableKind::Player),
+					b: object.object.get_id(),
+					b_type: Participant::Object(object.get_kind()),
+					point: get_distance(player.get_shape(), object.get_shape()).point_a,
+					normal,
+					relative_velocity: vec2_sub(player.velocity, object.velocity),
+					destroyed_for_points: if object.is_destroyed() { Some(object.get_points()) } else { None },
+				});
+			}
+		}
+	}
+
+	// Broad phase, balls are only tested against objects in the cells they can reach
+	state.object_grid.clear();
+	for (i, object) in objects_to_check_collision_against.iter().enumerate() {
+		state.object_grid.insert(i, &object.get_shape().get_aabb());
+	}
+	let mut candidates = Vec::new();
+
+	// Collide balls with collidable objects
+	for ball in &mut state.balls {
+		state.object_grid.query(&ball.get_swept_aabb(dt), &mut candidates);
+		stats.object_collisions += ball.update(&mut state.scene, &mut state.collision_events, dt, &mut objects_to_check_collision_against, &candidates);
+	}
+
+	state.objects.retain(|obj| !obj.is_destroyed());
+
+	// Balls touching an out of bounds zone in this step are lost right away
+	let lost = |ball: &BallObject, contacts: &[Contact]| contacts.iter().any(|contact| {
+		contact.a == ball.get_id() && contact.a_type == Participant::Ball && contact.b_type == Participant::Object(CollidableKind::OutOfBounds)
+	});
+	let contacts = state.collision_events.get_step_contacts();
+	for ball in state.balls.iter().filter(|ball| lost(ball, contacts)) {
+		state.scene.remove(ball.get_object());
+		stats.balls_lost += 1;
+	}
+	state.balls.retain(|ball| !lost(ball, contacts));
+
+	// Collide balls with other balls, along the whole way they moved so fast ones can not pass through each other
+	state.ball_grid.clear();
+	for (i, ball) in state.balls.iter().enumerate() {
+		state.ball_grid.insert(i, &ball.get_step_aabb());
+	}
+	let balls_count = state.balls.len();
+	for i in 0..balls_count {
+		let (_, ball, others) = split_vec_mut_around(&mut state.balls, i);
+		state.ball_grid.query(&ball.get_step_aabb(), &mut candidates);
+		// Each pair once, from the ball with the lower index
+		for &j in candidates.iter().filter(|&&j| j > i) {
+			if let Some(contact) = ball.collide_with_ball(&mut others[j - i - 1]) {
+				state.collision_events.add_contact(contact);
+				stats.ball_collisions += 1;
+			}
+		}
+	}
+	state.collision_events.end_step();
+
+	if state.balls.is_empty() {
+		state.lives = state.lives.saturating_sub(1);
+		if state.lives > 0 {
+			serve_new_ball(state);
+			state.game_state = GameState::Serve;
+		} else {
+			state.game_state = GameState::GameOver;
+		}
+	} else if !state.objects.iter().any(|obj| obj.get_kind() == CollidableKind::Brick) {
+		state.score.borrow_mut().on_level_complete(state.current_time_in_seconds);
+		state.game_state = GameState::LevelComplete;
+	}
+	stats
+}
+
+// Puts a ball right on top of the paddle
+fn serve_new_ball(state: &mut WebGame) {
+	let position = {
+		let player = &state.player.as_ref().unwrap().object;
+		// The paddle is a unit box scaled to size
+		[player.position[0], player.position[1] + player.scale[1] / 2.0 + SERVE_BALL_RADIUS + 0.5]
+	};
+	spawn_ball(state, &BallDesc {
+		position,
+		velocity: SERVE_VELOCITY,
+		radius: SERVE_BALL_RADIUS,
+		mass: DEFAULT_BALL_MASS,
+		restitution: DEFAULT_BALL_RESTITUTION,
+	});
+}
+
+// Returns how far the paddle moved
+fn move_player(state: &mut WebGame, dt: f64) -> f32 {
+	let mouse_x = state.mouse_x;
+	let player = state.player.as_mut().unwrap();
+	let offset = mouse_x - player.object.position[0];
+	let moved = state.last_mouse_x.map_or(0.0, |last| mouse_x - last);
+	player.velocity = [moved / dt as f32, 0.0];
+	state.last_mouse_x = Some(mouse_x);
+	player.object.position[0] = mouse_x;
+	player.update();
+	offset
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use render::NullBackend;
+	use broad_phase::UniformGrid;
+	use events::ContactPhase;
+	use score::BONUS_PER_SECOND;
+	use sat::{AabbObject, NarrowPhase};
+	use std::cell::Cell;
+	use std::rc::Rc;
+	use vecmath::*;
+
+	fn inside_arena(position: &[f32; 2]) -> bool {
+		position[0].abs() < 50.0 && position[1].abs() < 50.0
+	}
+
+	fn press(state: &mut WebGame, key: &str) {
+		state.on_button(key, true);
+		state.on_button(key, false);
+	}
+
+	fn ball_positions(state: &WebGame) -> Vec<[f32; 2]> {
+		state.balls.iter().map(|ball| ball.get_position()).collect()
+	}
+
+	// An update followed by the events dispatch the front ends do every frame
+	fn run_frame(state: &mut WebGame) -> FrameStats {
+		let stats = update(state, 1.0 / 60.0);
+		state.collision_events.dispatch();
+		stats
+	}
+
+	fn drop_ball(state: &mut WebGame, index: usize) {
+		let ball = &mut state.balls[index];
+		ball.set_position([0.0, -44.0]);
+		ball.set_velocity([0.0, -85.0]);
+	}
+
+	// Counts the overlaps it is told about
+	struct CountingTrigger(Rc<Cell<u32>>);
+
+	impl Collidable for CountingTrigger {
+		fn on_collision(&mut self, _object: &mut GameObject, _scene: &mut Scene, _disp: &Vector2<f32>, _normal: &Vector2<f32>) {
+			self.0.set(self.0.get() + 1);
+		}
+
+		fn get_kind(&self) -> CollidableKind {
+			CollidableKind::Wall
+		}
+
+		fn is_solid(&self) -> bool {
+			false
+		}
+	}
+
+	#[test]
+	fn test_initial_state() {
+		let mut state = WebGame::new(Box::new(NullBackend));
+		initial_render_call(&mut state);
+		assert!(state.player.is_some());
+		assert_eq!(state.balls.len(), 8);
+		assert_eq!(state.objects.len(), 28);
+		assert!(state.scene.contains(&state.player.as_ref().unwrap().object));
+		let changes = state.scene.take_changes();
+		assert_eq!(changes[0], SceneChange::Clear);
+		assert_eq!(changes.len(), 37);
+		assert!(state.scene.take_changes().is_empty());
+	}
+
+	#[test]
+	fn test_headless_simulation() {
+		let mut state = WebGame::new(Box::new(NullBackend));
+		initial_render_call(&mut state);
+
+		for frame in 0..10000 {
+			// Keep the game going through serves, level changes and game overs
+			if state.game_state != GameState::Playing {
+				press(&mut state, " ");
+			}
+			state.mouse_x = (frame as f32 * 0.01).sin() * 40.0;
+			run_frame(&mut state);
+
+			for ball in &state.balls {
+				let position = ball.get_position();
+				assert!(inside_arena(&position), "ball escaped to {:?} in frame {}", position, frame);
+			}
+		}
+	}
+
+	#[test]
+	fn test_destroyed_bricks_are_removed() {
+		let mut state = WebGame::new(Box::new(NullBackend));
+		initial_render_call(&mut state);
+		state.game_state = GameState::Playing;
+		let bricks_count = |state: &WebGame| state.objects.iter().filter(|obj| obj.get_kind() == CollidableKind::Brick).count();
+		let initial_bricks = bricks_count(&state);
+		state.scene.take_changes();
+
+		let mut balls_lost = 0;
+		for _ in 0..600 {
+			balls_lost += run_frame(&mut state).balls_lost;
+		}
+
+		assert!(bricks_count(&state) < initial_bricks);
+		for obj in &state.objects {
+			assert!(!obj.is_destroyed());
+			if obj.get_kind() != CollidableKind::OutOfBounds {
+				assert!(state.scene.contains(&obj.object));
+			}
+		}
+		let removed = state.scene.take_changes().iter().filter(|change| matches!(**change, SceneChange::Remove(_))).count();
+		assert_eq!(removed, initial_bricks - bricks_count(&state) + balls_lost as usize);
+	}
+
+	#[test]
+	fn test_attract_and_serve() {
+		let mut state = WebGame::new(Box::new(NullBackend));
+		initial_render_call(&mut state);
+		assert_eq!(state.game_state, GameState::Attract);
+		let positions = ball_positions(&state);
+		update(&mut state, 1.0 / 60.0);
+		assert_eq!(ball_positions(&state), positions);
+
+		press(&mut state, " ");
+		update(&mut state, 1.0 / 60.0);
+		assert_eq!(state.game_state, GameState::Serve);
+
+		// Balls follow the paddle but do not fly off
+		state.mouse_x = 10.0;
+		update(&mut state, 1.0 / 60.0);
+		assert_eq!(state.game_state, GameState::Serve);
+		for (ball, old) in state.balls.iter().zip(positions.iter()) {
+			assert_eq!(ball.get_position(), [old[0] + 10.0, old[1]]);
+		}
+
+		press(&mut state, " ");
+		update(&mut state, 1.0 / 60.0);
+		assert_eq!(state.game_state, GameState::Playing);
+		let positions = ball_positions(&state);
+		update(&mut state, 1.0 / 60.0);
+		assert!(ball_positions(&state) != positions);
+	}
+
+	#[test]
+	fn test_held_button_is_pressed_once() {
+		let mut state = WebGame::new(Box::new(NullBackend));
+		initial_render_call(&mut state);
+		state.on_button(" ", true);
+		update(&mut state, 1.0 / 60.0);
+		assert_eq!(state.game_state, GameState::Serve);
+
+		// Key repeat
+		state.on_button(" ", true);
+		update(&mut state, 1.0 / 60.0);
+		assert_eq!(state.game_state, GameState::Serve);
+	}
+
+	#[test]
+	fn test_pause() {
+		let mut state = WebGame::new(Box::new(NullBackend));
+		initial_render_call(&mut state);
+		state.game_state = GameState::Playing;
+
+		press(&mut state, "Escape");
+		update(&mut state, 1.0 / 60.0);
+		assert_eq!(state.game_state, GameState::Paused);
+		let positions = ball_positions(&state);
+		for _ in 0..10 {
+			update(&mut state, 1.0 / 60.0);
+		}
+		assert_eq!(ball_positions(&state), positions);
+
+		press(&mut state, "Escape");
+		update(&mut state, 1.0 / 60.0);
+		assert_eq!(state.game_state, GameState::Playing);
+		update(&mut state, 1.0 / 60.0);
+		assert!(ball_positions(&state) != positions);
+	}
+
+	#[test]
+	fn test_level_complete() {
+		let mut state = WebGame::new(Box::new(NullBackend));
+		initial_render_call(&mut state);
+		state.game_state = GameState::Playing;
+		state.objects.retain(|obj| obj.get_kind() != CollidableKind::Brick);
+
+		update(&mut state, 1.0 / 60.0);
+		assert_eq!(state.game_state, GameState::LevelComplete);
+
+		press(&mut state, " ");
+		update(&mut state, 1.0 / 60.0);
+		assert_eq!(state.game_state, GameState::Serve);
+		assert_eq!(state.level_index, 1);
+		assert!(state.objects.iter().any(|obj| obj.get_kind() == CollidableKind::Brick));
+	}
+
+	#[test]
+	fn test_game_over() {
+		let mut state = WebGame::new(Box::new(NullBackend));
+		initial_render_call(&mut state);
+		state.game_state = GameState::Playing;
+		state.level_index = 1;
+		state.lives = 1;
+		state.balls.clear();
+
+		update(&mut state, 1.0 / 60.0);
+		assert_eq!(state.game_state, GameState::GameOver);
+
+		press(&mut state, " ");
+		update(&mut state, 1.0 / 60.0);
+		assert_eq!(state.game_state, GameState::Serve);
+		assert_eq!(state.level_index, 0);
+		assert_eq!(state.balls.len(), 8);
+	}
+
+	#[test]
+	fn test_ball_lost() {
+		let mut state = WebGame::new(Box::new(NullBackend));
+		initial_render_call(&mut state);
+		state.game_state = GameState::Playing;
+		state.mouse_x = 30.0;
+		let balls_count = state.balls.len();
+
+		drop_ball(&mut state, 0);
+		let lost_id = state.balls[0].get_id();
+		let stats = update(&mut state, 1.0 / 60.0);
+		assert_eq!(stats.balls_lost, 1);
+		assert_eq!(state.balls.len(), balls_count - 1);
+		assert!(state.scene.take_changes().contains(&SceneChange::Remove(lost_id)));
+		// Other balls are still in play
+		assert_eq!(state.lives, STARTING_LIVES);
+		assert_eq!(state.game_state, GameState::Playing);
+	}
+
+	#[test]
+	fn test_last_ball_lost() {
+		let mut state = WebGame::new(Box::new(NullBackend));
+		initial_render_call(&mut state);
+		state.game_state = GameState::Playing;
+		state.mouse_x = 30.0;
+		state.balls.truncate(1);
+
+		drop_ball(&mut state, 0);
+		update(&mut state, 1.0 / 60.0);
+		assert_eq!(state.lives, STARTING_LIVES - 1);
+		assert_eq!(state.game_state, GameState::Serve);
+		assert_eq!(state.balls.len(), 1);
+		let position = state.balls[0].get_position();
+		assert_eq!(position[0], 30.0);
+		assert!(position[1] > -40.0);
+
+		// Served ball rides on the paddle
+		state.mouse_x = 20.0;
+		update(&mut state, 1.0 / 60.0);
+		assert_eq!(state.balls[0].get_position()[0], 20.0);
+	}
+
+	#[test]
+	fn test_out_of_lives() {
+		let mut state = WebGame::new(Box::new(NullBackend));
+		initial_render_call(&mut state);
+		state.game_state = GameState::Playing;
+		state.mouse_x = 30.0;
+		state.balls.truncate(1);
+		state.lives = 1;
+
+		drop_ball(&mut state, 0);
+		update(&mut state, 1.0 / 60.0);
+		assert_eq!(state.lives, 0);
+		assert_eq!(state.game_state, GameState::GameOver);
+		assert!(state.balls.is_empty());
+
+		press(&mut state, " ");
+		update(&mut state, 1.0 / 60.0);
+		assert_eq!(state.lives, STARTING_LIVES);
+		assert_eq!(state.game_state, GameState::Serve);
+	}
+
+	#[test]
+	fn test_score() {
+		let mut state = WebGame::new(Box::new(NullBackend));
+		initial_render_call(&mut state);
+		state.score.borrow_mut().points = 100;
+		press(&mut state, " ");
+		update(&mut state, 1.0 / 60.0);
+		// A new game starts from zero
+		assert_eq!(state.score.borrow().points, 0);
+
+		state.current_time_in_seconds = 5.0;
+		press(&mut state, " ");
+		update(&mut state, 1.0 / 60.0);
+		assert_eq!(state.score.borrow().level_started_at, Some(5.0));
+
+		// Knock out a single 1 hit point brick
+		state.balls.truncate(1);
+		state.objects.retain(|obj| obj.get_kind() != CollidableKind::Brick || obj.object.position == [35.0, 25.0, 2.5]);
+		state.balls[0].set_position([35.0, 20.0]);
+		state.balls[0].set_velocity([0.0, 85.0]);
+		state.current_time_in_seconds = 15.0;
+		for _ in 0..10 {
+			run_frame(&mut state);
+		}
+		assert_eq!(state.game_state, GameState::LevelComplete);
+		let bonus = (state.score.borrow().par_time as u32 - 10) * BONUS_PER_SECOND;
+		assert_eq!(state.score.borrow().points, 10 + bonus);
+	}
+
+	#[test]
+	fn test_paddle_resets_combo() {
+		let mut state = WebGame::new(Box::new(NullBackend));
+		initial_render_call(&mut state);
+		state.game_state = GameState::Playing;
+		state.balls.truncate(1);
+		state.score.borrow_mut().combo = 5;
+		state.balls[0].set_position([0.0, -35.0]);
+		state.balls[0].set_velocity([0.0, -85.0]);
+		update(&mut state, 1.0 / 60.0);
+		// Nothing changes until the events are dispatched
+		assert_eq!(state.score.borrow().combo, 5);
+		state.collision_events.dispatch();
+		assert_eq!(state.score.borrow().combo, 0);
+	}
+
+	#[test]
+	fn test_fixed_timestep() {
+		let mut state = WebGame::new(Box::new(NullBackend));
+		initial_render_call(&mut state);
+		state.game_state = GameState::Playing;
+		let mut timestep = FixedTimestep::new(64.0, 4);
+
+		// Less than a step, nothing moves yet
+		let positions = ball_positions(&state);
+		timestep.advance(&mut state, 1.0 / 128.0);
+		assert_eq!(ball_positions(&state), positions);
+		assert_eq!(timestep.get_alpha(), 0.5);
+
+		timestep.advance(&mut state, 1.0 / 128.0);
+		assert!(ball_positions(&state) != positions);
+		assert_eq!(timestep.get_alpha(), 0.0);
+
+		// A long frame is capped at max_steps
+		state.balls.truncate(1);
+		state.balls[0].set_position([0.0, 0.0]);
+		timestep.advance(&mut state, 10.0);
+		assert!(timestep.get_alpha() < 1.0);
+		let moved = state.balls[0].get_position();
+		assert!((moved[0] - 4.0 * 85.0 / 64.0).abs() < 0.001);
+	}
+
+	#[test]
+	fn test_independent_of_frame_rate() {
+		let run = |frame_time: f64, frames: u32| {
+			let mut state = WebGame::new(Box::new(NullBackend));
+			initial_render_call(&mut state);
+			state.game_state = GameState::Playing;
+			let mut timestep = FixedTimestep::new(128.0, 8);
+			for _ in 0..frames {
+				timestep.advance(&mut state, frame_time);
+			}
+			ball_positions(&state)
+		};
+		assert_eq!(run(1.0 / 64.0, 128), run(1.0 / 256.0, 512));
+	}
+
+	#[test]
+	fn test_interpolation() {
+		let mut state = WebGame::new(Box::new(NullBackend));
+		initial_render_call(&mut state);
+		state.game_state = GameState::Playing;
+		state.balls.truncate(1);
+		state.balls[0].set_position([0.0, 0.0]);
+		let mut timestep = FixedTimestep::new(64.0, 4);
+		timestep.advance(&mut state, 1.5 / 64.0);
+
+		let ball = &state.balls[0].get_object();
+		assert!(ball.is_interpolating());
+		let (position, _) = ball.get_interpolated_transform(timestep.get_alpha() as f32);
+		let expected = vec3_sub(ball.position, vec3_scale([85.0, 85.0, 0.0], 0.5 / 64.0));
+		assert!(vec3_len(vec3_sub(position, expected)) < 0.001);
+
+		// Objects that did not move are not synced again
+		let wall = &state.objects[0].object;
+		assert!(!wall.is_interpolating());
+		assert_eq!(wall.get_interpolated_transform(0.5), (wall.position, wall.rotation));
+	}
+
+	#[test]
+	fn test_fast_balls_do_not_tunnel() {
+		let mut state = WebGame::new(Box::new(NullBackend));
+		initial_render_call(&mut state);
+		state.game_state = GameState::Playing;
+		// Keep a brick out of the way so the level is not complete
+		state.objects.retain(|obj| obj.get_kind() != CollidableKind::Brick || obj.object.position[0] < -30.0);
+		state.balls.truncate(1);
+
+		// A hundred units per frame, far more than the top wall is thick
+		state.balls[0].set_position([0.0, 0.0]);
+		state.balls[0].set_velocity([0.0, 6000.0]);
+		let stats = update(&mut state, 1.0 / 60.0);
+		assert_eq!(stats.object_collisions, 1);
+		assert!(state.balls[0].get_velocity()[1] < 0.0);
+		assert!(inside_arena(&state.balls[0].get_position()));
+
+		// Same for the paddle
+		state.balls[0].set_position([0.0, -20.0]);
+		state.balls[0].set_velocity([0.0, -3000.0]);
+		let stats = update(&mut state, 1.0 / 60.0);
+		assert_eq!(stats.object_collisions, 1);
+		assert_eq!(state.balls.len(), 1);
+		assert!(state.balls[0].get_velocity()[1] > 0.0);
+		assert!(inside_arena(&state.balls[0].get_position()));
+	}
+
+	#[test]
+	fn test_fast_ball_hits_first_brick() {
+		let mut state = WebGame::new(Box::new(NullBackend));
+		initial_render_call(&mut state);
+		state.game_state = GameState::Playing;
+		state.balls.truncate(1);
+		let bricks_count = |state: &WebGame| state.objects.iter().filter(|obj| obj.get_kind() == CollidableKind::Brick).count();
+		let initial_bricks = bricks_count(&state);
+
+		// Straight up through three rows, only the bottom brick is hit
+		state.balls[0].set_position([35.0, 0.0]);
+		state.balls[0].set_velocity([0.0, 3000.0]);
+		update(&mut state, 1.0 / 60.0);
+		assert_eq!(bricks_count(&state), initial_bricks - 1);
+		assert!(!state.objects.iter().any(|obj| obj.object.position == [35.0, 25.0, 2.5]));
+		assert!(state.balls[0].get_velocity()[1] < 0.0);
+	}
+
+	#[test]
+	fn test_fast_balls_do_not_pass_through_each_other() {
+		let mut state = WebGame::new(Box::new(NullBackend));
+		initial_render_call(&mut state);
+		state.game_state = GameState::Playing;
+		state.balls.truncate(2);
+
+		// They would swap places within a single step without ever overlapping
+		state.balls[0].set_position([-10.0, 0.0]);
+		state.balls[0].set_velocity([1200.0, 0.0]);
+		state.balls[1].set_position([10.0, 0.0]);
+		state.balls[1].set_velocity([-1200.0, 0.0]);
+		let stats = update(&mut state, 1.0 / 60.0);
+		assert_eq!(stats.ball_collisions, 1);
+		assert!(state.balls[0].get_position()[0] < state.balls[1].get_position()[0]);
+		assert!(state.balls[0].get_velocity()[0] < 0.0);
+		assert!(state.balls[1].get_velocity()[0] > 0.0);
+	}
+
+	#[test]
+	fn test_broad_phase_matches_brute_force() {
+		let run = |cell_size: f32| {
+			let mut state = WebGame::new(Box::new(NullBackend));
+			initial_render_call(&mut state);
+			state.game_state = GameState::Playing;
+			// A single cell holds everything, so every ball is tested against every object
+			state.object_grid = UniformGrid::new(cell_size);
+			state.ball_grid = UniformGrid::new(cell_size);
+			let mut stats = FrameStats::default();
+			for frame in 0..600 {
+				state.mouse_x = (frame as f32 * 0.02).sin() * 40.0;
+				stats += update(&mut state, 1.0 / 120.0);
+				state.collision_events.dispatch();
+			}
+			(stats, ball_positions(&state), state.objects.len())
+		};
+		assert_eq!(run(10.0), run(1.0e6));
+	}
+
+	#[test]
+	fn test_paddle_aims_the_ball() {
+		let mut state = WebGame::new(Box::new(NullBackend));
+		initial_render_call(&mut state);
+		state.game_state = GameState::Playing;
+		state.balls.truncate(1);
+
+		// Straight down onto the right half of the paddle
+		state.balls[0].set_position([6.0, -34.0]);
+		state.balls[0].set_velocity([0.0, -85.0]);
+		for _ in 0..10 {
+			update(&mut state, 1.0 / 120.0);
+		}
+		let v = state.balls[0].get_velocity();
+		assert!(v[0] > 0.0 && v[1] > 0.0);
+		assert!((vec2_len(v) - 85.0).abs() < 0.001);
+	}
+
+	#[test]
+	fn test_paddle_speed_after_pause() {
+		let mut state = WebGame::new(Box::new(NullBackend));
+		initial_render_call(&mut state);
+		state.game_state = GameState::Playing;
+		update(&mut state, 1.0 / 60.0);
+		state.mouse_x = 1.0;
+		update(&mut state, 1.0 / 60.0);
+		assert!((state.player.as_ref().unwrap().velocity[0] - 60.0).abs() < 0.001);
+
+		press(&mut state, "Escape");
+		update(&mut state, 1.0 / 60.0);
+		state.mouse_x = 40.0;
+		update(&mut state, 1.0 / 60.0);
+		press(&mut state, "Escape");
+		update(&mut state, 1.0 / 60.0);
+		// The paddle jumps to the mouse without flinging the ball
+		update(&mut state, 1.0 / 60.0);
+		let player = state.player.as_ref().unwrap();
+		assert_eq!(player.object.position[0], 40.0);
+		assert_eq!(player.velocity, [0.0, 0.0]);
+	}
+
+	#[test]
+	fn test_paddle_speed_independent_of_steps_per_frame() {
+		let run = |tick_rate: f64| {
+			let mut state = WebGame::new(Box::new(NullBackend));
+			initial_render_call(&mut state);
+			state.game_state = GameState::Playing;
+			state.balls.truncate(1);
+			state.balls[0].set_position([0.0, -33.5]);
+			state.balls[0].set_velocity([0.0, -85.0]);
+			let mut timestep = FixedTimestep::new(tick_rate, 8);
+			// The mouse moves once per frame. The ball lands on the paddle late in the second
+			// frame, where the paddle is in the same place whatever the steps
+			for frame in 0..20 {
+				state.mouse_x = frame as f32 * 0.5;
+				timestep.advance(&mut state, 1.0 / 60.0);
+			}
+			state.balls[0].get_velocity()
+		};
+		let (one_step, two_steps) = (run(60.0), run(120.0));
+		assert!(vec2_len(vec2_sub(one_step, two_steps)) < 0.01, "{:?} {:?}", one_step, two_steps);
+	}
+
+	#[test]
+	fn test_world_raycast() {
+		let mut state = WebGame::new(Box::new(NullBackend));
+		initial_render_call(&mut state);
+
+		// The bottom row of bricks is hit first
+		let hit = state.raycast([35.0, 0.0], [0.0, 1.0], 100.0, |_| true).unwrap();
+		assert_eq!(hit.kind, CollidableKind::Brick);
+		assert!((hit.hit.distance - 23.0).abs() < 0.0001);
+
+		let hit = state.raycast([35.0, 0.0], [0.0, 1.0], 100.0, |obj| obj.get_kind() != CollidableKind::Brick).unwrap();
+		assert_eq!(hit.kind, CollidableKind::Wall);
+		assert!((hit.hit.distance - 47.5).abs() < 0.0001);
+
+		let hit = state.raycast([0.0, 0.0], [0.0, -1.0], 100.0, |_| true).unwrap();
+		assert_eq!(hit.kind, CollidableKind::Player);
+		assert_eq!(hit.id, state.player.as_ref().unwrap().object.get_id());
+		assert!((hit.hit.distance - 38.5).abs() < 0.0001);
+
+		// Beside the paddle there is only the out of bounds zone
+		let hit = state.raycast([30.0, 0.0], [0.0, -1.0], 100.0, |_| true).unwrap();
+		assert_eq!(hit.kind, CollidableKind::OutOfBounds);
+		assert_eq!(state.raycast([30.0, 0.0], [0.0, -1.0], 100.0, |obj| obj.is_solid()), None);
+
+		let hit = state.circle_cast([35.0, 0.0], 2.5, [0.0, 1.0], 100.0, |_| true).unwrap();
+		assert!((hit.hit.distance - 20.5).abs() < 0.0001);
+	}
+
+	#[test]
+	fn test_gjk_narrow_phase() {
+		let run = |narrow_phase: NarrowPhase| {
+			let mut state = WebGame::new(Box::new(NullBackend));
+			initial_render_call(&mut state);
+			state.game_state = GameState::Playing;
+			for object in &mut state.objects {
+				object.narrow_phase = narrow_phase;
+			}
+			let mut stats = FrameStats::default();
+			for frame in 0..600 {
+				state.mouse_x = (frame as f32 * 0.02).sin() * 40.0;
+				stats += update(&mut state, 1.0 / 120.0);
+				state.collision_events.dispatch();
+			}
+			(stats, ball_positions(&state))
+		};
+		let (sat_stats, sat_positions) = run(NarrowPhase::Sat);
+		let (gjk_stats, gjk_positions) = run(NarrowPhase::Gjk);
+		assert_eq!(sat_stats, gjk_stats);
+		for (sat, gjk) in sat_positions.iter().zip(gjk_positions.iter()) {
+			assert!(vec2_len(vec2_sub(*sat, *gjk)) < 0.01, "{:?} {:?}", sat, gjk);
+		}
+	}
+
+	#[test]
+	fn test_ball_passes_through_bricks() {
+		let mut state = WebGame::new(Box::new(NullBackend));
+		initial_render_call(&mut state);
+		state.game_state = GameState::Playing;
+		state.balls.truncate(1);
+		let bricks_count = |state: &WebGame| state.objects.iter().filter(|obj| obj.get_kind() == CollidableKind::Brick).count();
+		let initial_bricks = bricks_count(&state);
+
+		// Straight up through three rows to the top wall
+		state.balls[0].set_filter(CollisionFilter::new(LAYER_BALL, ALL_LAYERS & !LAYER_BRICK));
+		state.balls[0].set_position([35.0, 0.0]);
+		state.balls[0].set_velocity([0.0, 3000.0]);
+		let stats = update(&mut state, 1.0 / 60.0);
+		assert_eq!(stats.object_collisions, 1);
+		assert_eq!(bricks_count(&state), initial_bricks);
+		assert!(state.balls[0].get_velocity()[1] < 0.0);
+		assert!(inside_arena(&state.balls[0].get_position()));
+	}
+
+	#[test]
+	fn test_sensor_bricks() {
+		let mut state = WebGame::new(Box::new(NullBackend));
+		initial_render_call(&mut state);
+		state.game_state = GameState::Playing;
+		state.balls.truncate(1);
+		for obj in &mut state.objects {
+			obj.sensor = obj.get_kind() == CollidableKind::Brick;
+		}
+
+		// Every brick on the way is hit, only the top wall bounces the ball
+		state.balls[0].set_position([35.0, 0.0]);
+		state.balls[0].set_velocity([0.0, 3000.0]);
+		let stats = update(&mut state, 1.0 / 60.0);
+		assert_eq!(stats.object_collisions, 1);
+		assert!(!state.objects.iter().any(|obj| obj.object.position == [35.0, 25.0, 2.5]));
+		assert!(state.balls[0].get_velocity()[1] < 0.0);
+		assert_eq!(state.balls[0].get_velocity()[0], 0.0);
+	}
+
+	#[test]
+	fn test_sensor_reacts_to_paddle_only() {
+		let mut state = WebGame::new(Box::new(NullBackend));
+		initial_render_call(&mut state);
+		state.game_state = GameState::Playing;
+		state.mouse_x = -30.0;
+		state.balls.truncate(1);
+
+		let count = Rc::new(Cell::new(0));
+		let mut object = GameObject::new(100, &state.geometries.unit_box_geometry, &state.materials.walls, false, false);
+		object.position = [30.0, -40.0, 0.0];
+		let mut sensor = CollidableObject::new(object, Box::new(AabbObject::new([30.0, -40.0], [2.0, 2.0])), Box::new(CountingTrigger(count.clone())));
+		sensor.filter = CollisionFilter::new(LAYER_WALL, LAYER_PLAYER);
+		assert!(!sensor.is_solid());
+		state.objects.push(Box::new(sensor));
+
+		// A ball falls right through it
+		state.balls[0].set_position([30.0, -36.0]);
+		state.balls[0].set_velocity([0.0, -85.0]);
+		for _ in 0..4 {
+			update(&mut state, 1.0 / 60.0);
+		}
+		assert_eq!(count.get(), 0);
+		assert_eq!(state.balls[0].get_velocity(), [0.0, -85.0]);
+
+		state.mouse_x = 30.0;
+		state.collision_events.take_events();
+		update(&mut state, 1.0 / 60.0);
+		assert_eq!(count.get(), 1);
+		let events = state.collision_events.take_events();
+		assert_eq!(events[0].phase, ContactPhase::Begin);
+		assert_eq!(events[0].contact.a, state.player.as_ref().unwrap().object.get_id());
+		assert_eq!(events[0].contact.b, 100);
+	}
+
+	#[test]
+	fn test_collision_events() {
+		let mut state = WebGame::new(Box::new(NullBackend));
+		initial_render_call(&mut state);
+		state.game_state = GameState::Playing;
+		state.objects.retain(|obj| obj.get_kind() != CollidableKind::Brick || obj.object.position[0] < -30.0);
+		state.balls.truncate(1);
+		state.collision_events.take_events();
+
+		state.balls[0].set_position([0.0, 0.0]);
+		state.balls[0].set_velocity([0.0, 6000.0]);
+		update(&mut state, 1.0 / 60.0);
+		let events = state.collision_events.take_events();
+		assert_eq!(events.len(), 1);
+		let event = events[0];
+		assert_eq!(event.phase, ContactPhase::Begin);
+		assert_eq!(event.contact.a, state.balls[0].get_id());
+		assert_eq!(event.contact.b_type, Participant::Object(CollidableKind::Wall));
+		assert_eq!(event.contact.normal, [0.0, -1.0]);
+		assert_eq!(event.contact.relative_velocity, [0.0, 6000.0]);
+		// On top of the ball, where it touched the wall
+		let position = state.balls[0].get_position();
+		assert!(event.contact.point[1] > position[1]);
+
+		// Bounced away, so the contact ends
+		state.balls[0].set_velocity([0.0, -10.0]);
+		update(&mut state, 1.0 / 60.0);
+		let events = state.collision_events.take_events();
+		assert_eq!(events.len(), 1);
+		assert_eq!(events[0].phase, ContactPhase::End);
+		assert_eq!(events[0].contact, event.contact);
+	}
+}
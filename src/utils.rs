@@ -1,13 +1,14 @@
 pub fn min<T:PartialOrd>(a:T,b:T)->T { if a<b{a}else{b} }
 pub fn max<T:PartialOrd>(a:T,b:T)->T { if a>b{a}else{b} }
 
-pub fn split_vec_mut_around<T>(vec: &mut Vec<T>, index: usize) -> (&mut [T], &mut T, &mut [T]) {
+pub fn split_vec_mut_around<T>(vec: &mut [T], index: usize) -> (&mut [T], &mut T, &mut [T]) {
 	let (a, c) = vec.split_at_mut(index);
 	let (b, c) = c.split_at_mut(1);
 	(a, &mut b[0], c)
 }
 
 #[cfg(test)]
+#[allow(unused_variables)]
 mod tests {
     use utils::split_vec_mut_around;
 
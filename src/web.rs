@@ -0,0 +1,189 @@
+use stdweb::web::{
+    self,
+	IEventTarget,
+};
+
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use stdweb::web::event::{
+    IEvent,
+    IKeyboardEvent,
+    KeydownEvent,
+    KeyupEvent,
+};
+
+use vecmath::Vector3;
+use wasm_test::utils::{min, max};
+use wasm_test::game::*;
+use wasm_test::render::RenderBackend;
+use wasm_test::simulation::{initial_render_call, FixedTimestep};
+
+macro_rules! enclose {
+    ( [$( $x:ident ),*] $y:expr ) => {
+        {
+            $(let $x = $x.clone();)*
+            $y
+        }
+    };
+}
+
+thread_local!(
+	static STATE: Rc<RefCell<WebGame>> = Rc::new(RefCell::new(WebGame::new(Box::new(ThreeJsBackend))));
+	static TIMESTEP: RefCell<FixedTimestep> = RefCell::new(FixedTimestep::default());
+);
+
+// Drives the three.js scene set up in code.js through its global object tables
+pub struct ThreeJsBackend;
+
+impl RenderBackend for ThreeJsBackend {
+	fn create_mesh(&mut self, id: u32, geometry: u32, material: u32, cast_shadows: bool, receive_shadows: bool) {
+		js!(
+			var temp = new THREE.Mesh(geometry[@{geometry}], materials[@{material}]);
+			temp.castShadow = @{cast_shadows};
+			temp.receiveShadow = @{receive_shadows};
+			gameObjects[@{id}] = temp;
+		);
+	}
+
+	fn set_transform(&mut self, id: u32, position: Vector3<f32>, rotation: f32, scale: Vector3<f32>) {
+		js!(
+			var obj = gameObjects[@{id}];
+			obj.position.x = @{position[0]};
+			obj.position.y = @{position[1]};
+			obj.position.z = @{position[2]};
+			obj.rotation.z = @{rotation};
+			obj.scale.x = @{scale[0]};
+			obj.scale.y = @{scale[1]};
+			obj.scale.z = @{scale[2]};
+		);
+	}
+
+	fn set_material(&mut self, id: u32, material: u32) {
+		js!(
+			gameObjects[@{id}].material = materials[@{material}];
+		);
+	}
+
+	fn add_to_scene(&mut self, id: u32) {
+		js!(
+			scene.add(gameObjects[@{id}]);
+		);
+	}
+
+	fn remove_from_scene(&mut self, id: u32) {
+		js!(
+			scene.remove(gameObjects[@{id}]);
+			delete gameObjects[@{id}];
+		);
+	}
+
+	fn clear_scene(&mut self) {
+		js!(
+			for (var id in gameObjects) {
+				scene.remove(gameObjects[id]);
+			}
+			gameObjects = {};
+		);
+	}
+
+	fn render(&mut self) {
+		js!(
+			renderer.render(scene, camera);
+		);
+	}
+}
+
+fn render(current_stamp: f64, dt: f64) {
+	STATE.with(|state| {
+		let mut state = state.borrow_mut();
+		state.current_time_in_seconds = current_stamp / 1000.0;
+		let alpha = if dt == 0.0 {
+			initial_render_call(&mut state);
+			1.0
+		} else {
+			TIMESTEP.with(|timestep| {
+				let mut timestep = timestep.borrow_mut();
+				timestep.advance(&mut state, dt);
+				timestep.get_alpha()
+			})
+		};
+		state.collision_events.dispatch();
+		state.render(alpha as f32);
+	});
+	web::window().request_animation_frame(move |stamp| {
+		render(stamp, (stamp - current_stamp) / 1000.0);
+	});
+}
+
+fn on_mouse_move(x: i32, y: i32) {
+	STATE.with(|state| {
+		let mut state = state.borrow_mut();
+
+		//Transform from top right corner to screen coordinates
+		let aspect = state.screen_w as f32 / state.screen_h as f32;
+		let real_width = state.frustum_size * aspect;
+		let half_w = state.screen_w as f32 / 2.0;
+		let half_h = state.screen_h as f32 / 2.0;
+
+		state.mouse_x = (x as f32 - half_w) / state.screen_w as f32 * real_width;
+		state.mouse_x = min(max(state.mouse_x, (-state.frustum_size / 2.0) + 10.0), state.frustum_size / 2.0 - 10.0);
+
+		// Does not matter for now
+		state.mouse_y = y as f32 - half_h;
+	});
+}
+
+fn on_sceen_size_changed(w: i32, h: i32) {
+	STATE.with(|state| {
+		let mut state = state.borrow_mut();
+		state.screen_w = w;
+		state.screen_h = h;
+	});
+}
+
+fn get_score() -> u32 {
+	STATE.with(|state| state.borrow().score.borrow().points)
+}
+
+fn get_multiplier() -> u32 {
+	STATE.with(|state| state.borrow().score.borrow().get_multiplier())
+}
+
+fn get_lives() -> u32 {
+	STATE.with(|state| state.borrow().lives)
+}
+
+fn prepare_button_listener(game: Rc<RefCell<WebGame>>) {
+	stdweb::web::window().add_event_listener(enclose!([game] move |event: KeydownEvent| {
+		if game.borrow_mut().on_button(&event.key(), true) {
+			event.prevent_default();
+		}
+	}));
+	stdweb::web::window().add_event_listener(enclose!([game] move |event: KeyupEvent| {
+		if game.borrow_mut().on_button(&event.key(), false) {
+			event.prevent_default();
+		}
+	}));
+}
+
+fn init() {
+	STATE.with(|state| {
+		prepare_button_listener(state.clone());
+	});
+	stdweb::web::window().request_animation_frame(move |stamp: f64| {
+		render(stamp, 0.0);
+	});
+}
+
+pub fn main() {
+	stdweb::initialize();
+	js! {
+        Module.exports.on_mouse_move = @{on_mouse_move};
+        Module.exports.on_sceen_size_changed = @{on_sceen_size_changed};
+        Module.exports.init = @{init};
+        Module.exports.get_score = @{get_score};
+        Module.exports.get_multiplier = @{get_multiplier};
+        Module.exports.get_lives = @{get_lives};
+    }
+}